@@ -1,26 +1,106 @@
+mod persistence;
+mod range;
+mod volume;
+
+use core::time::Duration;
+use std::path::PathBuf;
+
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
-use bevy_kira_audio::prelude::Decibels;
 #[cfg(feature = "reflect")]
 use bevy_reflect::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use self::persistence::SoundConfigPersistencePlugin;
+pub use self::persistence::{
+    DEFAULT_SAVE_DEBOUNCE, SOUND_CONFIG_FILE_NAME, SOUND_CONFIG_VERSION,
+    SoundConfigPersistenceError, load_sound_config, save_sound_config,
+};
+pub use self::{
+    range::{InvalidVolumeRange, MAX_SLIDER_POSITION, VolumeRange},
+    volume::Decibels,
+};
+
 pub mod prelude {
     #[doc(hidden)]
-    pub use crate::{SoundConfig, SoundConfigPlugin};
+    pub use crate::{SoundCategory, SoundConfig, SoundConfigPlugin};
 }
 
-pub struct SoundConfigPlugin;
+#[derive(Clone, Default)]
+pub struct SoundConfigPlugin {
+    /// The directory the sound config is persisted to, if any.
+    directory: Option<PathBuf>,
+    save_debounce: Option<Duration>,
+}
+
+impl SoundConfigPlugin {
+    /// Loads the sound config from a RON file in the given directory at
+    /// startup, and saves it there whenever it changes.
+    ///
+    /// A missing, corrupt or invalid file falls back to the default sound
+    /// config.
+    pub fn persisted(directory: impl Into<PathBuf>) -> Self {
+        SoundConfigPlugin {
+            directory: Some(directory.into()),
+            save_debounce: None,
+        }
+    }
+
+    /// Sets how long to wait after the last change before saving, so dragging
+    /// a volume slider does not write the file every frame.
+    pub fn with_save_debounce(mut self, save_debounce: Duration) -> Self {
+        self.save_debounce = Some(save_debounce);
+        self
+    }
+}
 
 impl Plugin for SoundConfigPlugin {
-    fn build(&self, #[allow(unused_variables)] app: &mut App) {
+    fn build(&self, app: &mut App) {
         #[cfg(feature = "reflect")]
-        app.register_type::<SoundConfig>();
+        {
+            app.register_type::<SoundCategory>();
+            app.register_type::<SoundConfig>();
+        }
 
-        app.insert_resource(SoundConfig::default());
+        match &self.directory {
+            Some(directory) => {
+                app.add_plugins(SoundConfigPersistencePlugin {
+                    directory: directory.clone(),
+                    save_debounce: self.save_debounce.unwrap_or(DEFAULT_SAVE_DEBOUNCE),
+                });
+            }
+            None => {
+                app.insert_resource(SoundConfig::default());
+            }
+        }
     }
 }
 
+/// The mixing category of a sound, each with its own volume in the
+/// [`SoundConfig`].
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[cfg_attr(
+    feature = "reflect",
+    derive(Reflect),
+    reflect(Debug, Default, Deserialize, Hash, PartialEq, Serialize)
+)]
+pub enum SoundCategory {
+    Music,
+    #[default]
+    SoundEffect,
+    VoiceOver,
+    Dialogue,
+}
+
+impl SoundCategory {
+    pub const ALL: [SoundCategory; 4] = [
+        SoundCategory::Music,
+        SoundCategory::SoundEffect,
+        SoundCategory::VoiceOver,
+        SoundCategory::Dialogue,
+    ];
+}
+
 #[derive(Clone, Default, Deserialize, Resource, Serialize)]
 #[cfg_attr(feature = "debug", derive(Debug))]
 #[cfg_attr(
@@ -29,6 +109,8 @@ impl Plugin for SoundConfigPlugin {
     reflect(Default, Deserialize, Resource, Serialize)
 )]
 #[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+// Fields missing from a persisted sound config keep their default.
+#[serde(default)]
 pub struct SoundConfig {
     pub global_volume_decibels: f32,
     original_global_volume_decibels: Option<f32>,
@@ -36,6 +118,14 @@ pub struct SoundConfig {
     pub sound_effect_volume_decibels: f32,
     pub voice_over_volume_decibels: f32,
     pub dialogue_volume_decibels: f32,
+    pub music_muted: bool,
+    pub sound_effect_muted: bool,
+    pub voice_over_muted: bool,
+    pub dialogue_muted: bool,
+    /// The only category that is audible, for audio debugging.
+    pub solo: Option<SoundCategory>,
+    /// The range every volume is clamped to.
+    pub volume_range: VolumeRange,
 }
 
 impl SoundConfig {
@@ -49,7 +139,7 @@ impl SoundConfig {
 
     pub fn mute(&mut self) {
         self.original_global_volume_decibels = Some(self.global_volume_decibels);
-        self.global_volume_decibels = Decibels::SILENCE.0;
+        self.global_volume_decibels = self.volume_range.min_decibels();
     }
 
     pub fn unmute(&mut self) {
@@ -60,6 +150,134 @@ impl SoundConfig {
         self.original_global_volume_decibels = None;
     }
 
+    /// Sets the global volume, clamped to the volume range.
+    pub fn set_global_volume_decibels(&mut self, decibels: f32) {
+        self.global_volume_decibels = self.volume_range.clamp(decibels);
+    }
+
+    /// The global volume as a slider position from 0 to
+    /// [`MAX_SLIDER_POSITION`].
+    #[inline(always)]
+    pub fn global_volume_slider(&self) -> f32 {
+        self.volume_range
+            .slider_from_decibels(self.global_volume_decibels)
+    }
+
+    /// Sets the global volume from a slider position from 0 to
+    /// [`MAX_SLIDER_POSITION`]. See [`VolumeRange::decibels_from_slider`].
+    pub fn set_global_volume_slider(&mut self, position: f32) {
+        self.global_volume_decibels = self.volume_range.decibels_from_slider(position);
+    }
+
+    #[inline(always)]
+    pub fn category_volume_decibels(&self, category: SoundCategory) -> f32 {
+        match category {
+            SoundCategory::Music => self.music_volume_decibels,
+            SoundCategory::SoundEffect => self.sound_effect_volume_decibels,
+            SoundCategory::VoiceOver => self.voice_over_volume_decibels,
+            SoundCategory::Dialogue => self.dialogue_volume_decibels,
+        }
+    }
+
+    #[inline(always)]
+    fn category_volume_decibels_mut(&mut self, category: SoundCategory) -> &mut f32 {
+        match category {
+            SoundCategory::Music => &mut self.music_volume_decibels,
+            SoundCategory::SoundEffect => &mut self.sound_effect_volume_decibels,
+            SoundCategory::VoiceOver => &mut self.voice_over_volume_decibels,
+            SoundCategory::Dialogue => &mut self.dialogue_volume_decibels,
+        }
+    }
+
+    /// Sets the volume of the given category, clamped to the volume range.
+    pub fn set_category_volume_decibels(&mut self, category: SoundCategory, decibels: f32) {
+        *self.category_volume_decibels_mut(category) = self.volume_range.clamp(decibels);
+    }
+
+    /// The volume of the given category as a slider position from 0 to
+    /// [`MAX_SLIDER_POSITION`].
+    #[inline(always)]
+    pub fn category_volume_slider(&self, category: SoundCategory) -> f32 {
+        self.volume_range
+            .slider_from_decibels(self.category_volume_decibels(category))
+    }
+
+    /// Sets the volume of the given category from a slider position from 0 to
+    /// [`MAX_SLIDER_POSITION`]. See [`VolumeRange::decibels_from_slider`].
+    pub fn set_category_volume_slider(&mut self, category: SoundCategory, position: f32) {
+        *self.category_volume_decibels_mut(category) =
+            self.volume_range.decibels_from_slider(position);
+    }
+
+    pub fn toggle_category_mute(&mut self, category: SoundCategory) {
+        let muted = self.category_muted_mut(category);
+        *muted = !*muted;
+    }
+
+    pub fn mute_category(&mut self, category: SoundCategory) {
+        *self.category_muted_mut(category) = true;
+    }
+
+    pub fn unmute_category(&mut self, category: SoundCategory) {
+        *self.category_muted_mut(category) = false;
+    }
+
+    #[inline(always)]
+    pub fn is_category_muted(&self, category: SoundCategory) -> bool {
+        match category {
+            SoundCategory::Music => self.music_muted,
+            SoundCategory::SoundEffect => self.sound_effect_muted,
+            SoundCategory::VoiceOver => self.voice_over_muted,
+            SoundCategory::Dialogue => self.dialogue_muted,
+        }
+    }
+
+    #[inline(always)]
+    fn category_muted_mut(&mut self, category: SoundCategory) -> &mut bool {
+        match category {
+            SoundCategory::Music => &mut self.music_muted,
+            SoundCategory::SoundEffect => &mut self.sound_effect_muted,
+            SoundCategory::VoiceOver => &mut self.voice_over_muted,
+            SoundCategory::Dialogue => &mut self.dialogue_muted,
+        }
+    }
+
+    /// Solos the given category, or stops soloing if it is already soloed.
+    pub fn toggle_solo(&mut self, category: SoundCategory) {
+        if self.solo == Some(category) {
+            self.unsolo();
+        } else {
+            self.solo(category);
+        }
+    }
+
+    /// Makes the given category the only audible one. Mutes still apply to it.
+    pub fn solo(&mut self, category: SoundCategory) {
+        self.solo = Some(category);
+    }
+
+    pub fn unsolo(&mut self) {
+        self.solo = None;
+    }
+
+    /// Returns `true` if the category is neither muted nor silenced by another
+    /// category's solo.
+    #[inline(always)]
+    pub fn is_category_audible(&self, category: SoundCategory) -> bool {
+        !self.is_category_muted(category) && self.solo.is_none_or(|solo| solo == category)
+    }
+
+    /// The volume of the given category, on top of the base volume.
+    #[inline(always)]
+    pub fn category_volume(&self, category: SoundCategory, base: impl Into<Decibels>) -> Decibels {
+        match category {
+            SoundCategory::Music => self.music_volume(base),
+            SoundCategory::SoundEffect => self.sound_effect_volume(base),
+            SoundCategory::VoiceOver => self.voice_over_volume(base),
+            SoundCategory::Dialogue => self.dialogue_volume(base),
+        }
+    }
+
     #[inline(always)]
     pub fn music_volume(&self, base: impl Into<Decibels>) -> Decibels {
         Decibels(self.effective_music_volume().0 + base.into().0)
@@ -72,7 +290,7 @@ impl SoundConfig {
 
     #[inline(always)]
     fn effective_music_volume_decibels(&self) -> f32 {
-        self.effective_volume(self.music_volume_decibels)
+        self.effective_volume(SoundCategory::Music, self.music_volume_decibels)
     }
 
     #[inline(always)]
@@ -87,7 +305,10 @@ impl SoundConfig {
 
     #[inline(always)]
     fn effective_sound_effect_volume_decibels(&self) -> f32 {
-        self.effective_volume(self.sound_effect_volume_decibels)
+        self.effective_volume(
+            SoundCategory::SoundEffect,
+            self.sound_effect_volume_decibels,
+        )
     }
 
     #[inline(always)]
@@ -102,7 +323,7 @@ impl SoundConfig {
 
     #[inline(always)]
     fn effective_voice_over_volume_decibels(&self) -> f32 {
-        self.effective_volume(self.voice_over_volume_decibels)
+        self.effective_volume(SoundCategory::VoiceOver, self.voice_over_volume_decibels)
     }
 
     #[inline(always)]
@@ -117,11 +338,74 @@ impl SoundConfig {
 
     #[inline(always)]
     fn effective_dialogue_volume_decibels(&self) -> f32 {
-        self.effective_volume(self.dialogue_volume_decibels)
+        self.effective_volume(SoundCategory::Dialogue, self.dialogue_volume_decibels)
     }
 
     #[inline(always)]
-    fn effective_volume(&self, volume_decibels: f32) -> f32 {
-        self.global_volume_decibels + volume_decibels
+    fn effective_volume(&self, category: SoundCategory, volume_decibels: f32) -> f32 {
+        let range = &self.volume_range;
+        if !self.is_category_audible(category)
+            || range.is_silent(self.global_volume_decibels)
+            || range.is_silent(volume_decibels)
+        {
+            // Stays silent whatever base volume is added on top.
+            return f32::NEG_INFINITY;
+        }
+        range.clamp(self.global_volume_decibels) + range.clamp(volume_decibels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn category_volume_adds_global_and_base() {
+        let sound_config = SoundConfig {
+            global_volume_decibels: -6.0,
+            music_volume_decibels: -12.0,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            sound_config.category_volume(SoundCategory::Music, -3.0).0,
+            -21.0
+        );
+    }
+
+    #[test]
+    fn silent_category_volume_is_negative_infinity() {
+        let mut sound_config = SoundConfig::default();
+        sound_config.mute_category(SoundCategory::Music);
+        assert_eq!(
+            sound_config.category_volume(SoundCategory::Music, 6.0).0,
+            f32::NEG_INFINITY
+        );
+
+        let mut sound_config = SoundConfig::default();
+        sound_config.solo(SoundCategory::Dialogue);
+        assert_eq!(
+            sound_config.category_volume(SoundCategory::Music, 0.0).0,
+            f32::NEG_INFINITY
+        );
+
+        let mut sound_config = SoundConfig::default();
+        sound_config.mute();
+        assert_eq!(
+            sound_config.category_volume(SoundCategory::Music, 0.0).0,
+            f32::NEG_INFINITY
+        );
+        sound_config.unmute();
+        assert_eq!(
+            sound_config.category_volume(SoundCategory::Music, 0.0).0,
+            0.0
+        );
+
+        let mut sound_config = SoundConfig::default();
+        sound_config.set_category_volume_slider(SoundCategory::Music, 0.0);
+        assert_eq!(
+            sound_config.category_volume(SoundCategory::Music, 0.0).0,
+            f32::NEG_INFINITY
+        );
     }
 }
@@ -0,0 +1,448 @@
+use core::time::Duration;
+use std::path::{Path, PathBuf};
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_time::prelude::*;
+use derive_more::derive::{Display, Error, From};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use tracing::*;
+
+use crate::SoundConfig;
+
+/// The file name the [`SoundConfig`] is persisted to within its directory.
+pub const SOUND_CONFIG_FILE_NAME: &str = "sound_config.ron";
+
+/// The current version of the persisted [`SoundConfig`] schema.
+///
+/// Bump this when a change to [`SoundConfig`] cannot be handled by
+/// `#[serde(default)]` alone, and migrate the older versions in
+/// [`SoundConfigFile::migrate`].
+pub const SOUND_CONFIG_VERSION: u32 = 1;
+
+/// The default time to wait after the last change before saving.
+pub const DEFAULT_SAVE_DEBOUNCE: Duration = Duration::from_secs(1);
+
+pub(crate) struct SoundConfigPersistencePlugin {
+    pub(crate) directory: PathBuf,
+    pub(crate) save_debounce: Duration,
+}
+
+impl Plugin for SoundConfigPersistencePlugin {
+    fn build(&self, app: &mut App) {
+        let path = self.directory.join(SOUND_CONFIG_FILE_NAME);
+
+        let sound_config = match load_sound_config(&path) {
+            Ok(Some(sound_config)) => {
+                debug!(?path, "Loaded sound config");
+                sound_config
+            }
+            Ok(None) => {
+                debug!(?path, "No sound config to load, using defaults");
+                SoundConfig::default()
+            }
+            Err(error) => {
+                warn!(?path, %error, "Could not load sound config, using defaults");
+                SoundConfig::default()
+            }
+        };
+
+        app.insert_resource(sound_config);
+        app.insert_resource(SoundConfigStorage {
+            path,
+            debounce: Timer::new(self.save_debounce, TimerMode::Once),
+            pending: false,
+        });
+
+        app.add_systems(Update, save_sound_config_on_change);
+        app.add_systems(Last, save_sound_config_on_exit);
+    }
+}
+
+/// Possible errors when loading or saving the [`SoundConfig`].
+#[non_exhaustive]
+#[derive(Debug, Display, Error, From)]
+pub enum SoundConfigPersistenceError {
+    /// An [IO](std::io) error.
+    #[display("could not access sound config file: {_0}")]
+    Io(std::io::Error),
+    /// The file is not valid RON or does not match the schema.
+    #[display("could not parse sound config file: {_0}")]
+    Parse(ron::error::SpannedError),
+    /// The sound config could not be written as RON.
+    #[display("could not serialize sound config: {_0}")]
+    Serialize(ron::Error),
+    /// The file parsed, but a volume in it is not a finite number.
+    #[display("sound config has an invalid {field}: {decibels}")]
+    #[from(skip)]
+    Invalid { field: &'static str, decibels: f32 },
+}
+
+/// The on-disk layout of the persisted [`SoundConfig`].
+#[derive(Deserialize, Serialize)]
+struct SoundConfigFile {
+    version: u32,
+    config: SoundConfig,
+}
+
+/// Only the version of a persisted [`SoundConfig`], read before the rest of
+/// the file so older versions can be migrated.
+#[derive(Deserialize)]
+struct SoundConfigFileVersion {
+    version: u32,
+}
+
+impl SoundConfigFile {
+    /// Brings a file of any version up to [`SOUND_CONFIG_VERSION`].
+    fn migrate(contents: &str, version: u32) -> Result<SoundConfig, SoundConfigPersistenceError> {
+        if version > SOUND_CONFIG_VERSION {
+            // Written by a newer build, keep the fields we know about.
+            warn!(
+                version,
+                current_version = SOUND_CONFIG_VERSION,
+                "Sound config is from a newer version"
+            );
+        }
+
+        // Version 1 is the first version, so there is nothing to migrate yet.
+        let file = ron::from_str::<SoundConfigFile>(contents)?;
+        validate(&file.config)?;
+
+        Ok(file.config)
+    }
+}
+
+/// Rejects a parsed [`SoundConfig`] that would break mixing, so the default is
+/// used instead.
+///
+/// The [`VolumeRange`](crate::VolumeRange) validates itself while parsing.
+fn validate(sound_config: &SoundConfig) -> Result<(), SoundConfigPersistenceError> {
+    let volumes = [
+        (
+            "global_volume_decibels",
+            Some(sound_config.global_volume_decibels),
+        ),
+        (
+            "original_global_volume_decibels",
+            sound_config.original_global_volume_decibels,
+        ),
+        (
+            "music_volume_decibels",
+            Some(sound_config.music_volume_decibels),
+        ),
+        (
+            "sound_effect_volume_decibels",
+            Some(sound_config.sound_effect_volume_decibels),
+        ),
+        (
+            "voice_over_volume_decibels",
+            Some(sound_config.voice_over_volume_decibels),
+        ),
+        (
+            "dialogue_volume_decibels",
+            Some(sound_config.dialogue_volume_decibels),
+        ),
+    ];
+
+    for (field, decibels) in volumes {
+        if let Some(decibels) = decibels
+            && !decibels.is_finite()
+        {
+            return Err(SoundConfigPersistenceError::Invalid { field, decibels });
+        }
+    }
+
+    Ok(())
+}
+
+/// The path the [`SoundConfig`] is persisted to, and the debounce of pending
+/// saves.
+#[derive(Resource)]
+struct SoundConfigStorage {
+    path: PathBuf,
+    debounce: Timer,
+    /// Whether the sound config changed since it was last saved.
+    pending: bool,
+}
+
+impl SoundConfigStorage {
+    fn save(&mut self, sound_config: &SoundConfig) {
+        self.pending = false;
+
+        match save_sound_config(&self.path, sound_config) {
+            Ok(()) => debug!(path = ?self.path, "Saved sound config"),
+            Err(error) => error!(path = ?self.path, %error, "Could not save sound config"),
+        }
+    }
+}
+
+/// Loads the [`SoundConfig`] from the given file, or returns `None` if the
+/// file does not exist.
+pub fn load_sound_config(path: &Path) -> Result<Option<SoundConfig>, SoundConfigPersistenceError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+
+    let SoundConfigFileVersion { version } = ron::from_str(&contents)?;
+
+    SoundConfigFile::migrate(&contents, version).map(Some)
+}
+
+/// Saves the [`SoundConfig`] to the given file, creating its directory if
+/// needed.
+pub fn save_sound_config(
+    path: &Path,
+    sound_config: &SoundConfig,
+) -> Result<(), SoundConfigPersistenceError> {
+    let contents = ron::ser::to_string_pretty(
+        &SoundConfigFile {
+            version: SOUND_CONFIG_VERSION,
+            config: sound_config.clone(),
+        },
+        PrettyConfig::default(),
+    )?;
+
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+
+    // Write to a temporary file first so a crash mid-write does not corrupt
+    // the existing settings.
+    let temporary_path = path.with_extension("ron.tmp");
+    std::fs::write(&temporary_path, contents)?;
+    std::fs::rename(&temporary_path, path)?;
+
+    Ok(())
+}
+
+fn save_sound_config_on_change(
+    time: Res<Time>,
+    sound_config: Res<SoundConfig>,
+    mut storage: ResMut<SoundConfigStorage>,
+) {
+    // The sound config was just loaded, there is nothing new to save.
+    if sound_config.is_added() {
+        return;
+    }
+
+    if sound_config.is_changed() {
+        storage.pending = true;
+        storage.debounce.reset();
+    }
+
+    if !storage.pending {
+        return;
+    }
+
+    if storage.debounce.tick(time.delta()).is_finished() {
+        storage.save(&sound_config);
+    }
+}
+
+/// Saves a pending change that is still being debounced when the app exits.
+fn save_sound_config_on_exit(
+    mut exit: MessageReader<AppExit>,
+    sound_config: Res<SoundConfig>,
+    mut storage: ResMut<SoundConfigStorage>,
+) {
+    if exit.read().last().is_some() && storage.pending {
+        storage.save(&sound_config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SoundCategory;
+
+    /// A fresh directory under the system temporary directory for one test.
+    fn test_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("sound_config_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn round_trip() {
+        let directory = test_directory("round_trip");
+        let path = directory.join(SOUND_CONFIG_FILE_NAME);
+
+        let sound_config = SoundConfig {
+            global_volume_decibels: -6.0,
+            music_volume_decibels: -12.0,
+            sound_effect_muted: true,
+            solo: Some(SoundCategory::Dialogue),
+            ..Default::default()
+        };
+        save_sound_config(&path, &sound_config).unwrap();
+        let loaded = load_sound_config(&path).unwrap().unwrap();
+
+        assert_eq!(loaded.global_volume_decibels, -6.0);
+        assert_eq!(loaded.music_volume_decibels, -12.0);
+        assert!(loaded.sound_effect_muted);
+        assert!(!loaded.music_muted);
+        assert_eq!(loaded.solo, Some(SoundCategory::Dialogue));
+        assert!(!path.with_extension("ron.tmp").exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn missing_file_loads_nothing() {
+        let directory = test_directory("missing_file");
+
+        let loaded = load_sound_config(&directory.join(SOUND_CONFIG_FILE_NAME)).unwrap();
+
+        assert!(loaded.is_none());
+    }
+
+    #[test]
+    fn corrupt_file_is_an_error() {
+        let directory = test_directory("corrupt_file");
+        let path = directory.join(SOUND_CONFIG_FILE_NAME);
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(&path, "not a sound config").unwrap();
+
+        let loaded = load_sound_config(&path);
+
+        assert!(matches!(loaded, Err(SoundConfigPersistenceError::Parse(_))));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn corrupt_file_falls_back_to_defaults() {
+        let directory = test_directory("corrupt_fallback");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join(SOUND_CONFIG_FILE_NAME),
+            "(version: 1, config: ",
+        )
+        .unwrap();
+
+        let mut app = App::new();
+        app.add_plugins(SoundConfigPersistencePlugin {
+            directory: directory.clone(),
+            save_debounce: DEFAULT_SAVE_DEBOUNCE,
+        });
+
+        let sound_config = app.world().resource::<SoundConfig>();
+        let default = SoundConfig::default();
+        assert_eq!(
+            sound_config.global_volume_decibels,
+            default.global_volume_decibels
+        );
+        assert_eq!(
+            sound_config.music_volume_decibels,
+            default.music_volume_decibels
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn missing_version_is_an_error() {
+        let directory = test_directory("missing_version");
+        let path = directory.join(SOUND_CONFIG_FILE_NAME);
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(&path, "(config: (music_volume_decibels: -3.0))").unwrap();
+
+        let loaded = load_sound_config(&path);
+
+        assert!(matches!(loaded, Err(SoundConfigPersistenceError::Parse(_))));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn current_version_loads_with_missing_fields_defaulted() {
+        let contents =
+            format!("(version: {SOUND_CONFIG_VERSION}, config: (music_volume_decibels: -3.0))");
+
+        let sound_config = SoundConfigFile::migrate(&contents, SOUND_CONFIG_VERSION).unwrap();
+
+        assert_eq!(sound_config.music_volume_decibels, -3.0);
+        assert_eq!(
+            sound_config.global_volume_decibels,
+            SoundConfig::default().global_volume_decibels
+        );
+    }
+
+    #[test]
+    fn newer_version_keeps_known_fields() {
+        let directory = test_directory("newer_version");
+        let path = directory.join(SOUND_CONFIG_FILE_NAME);
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            &path,
+            format!(
+                "(version: {}, config: (music_volume_decibels: -3.0, reverb_decibels: -9.0))",
+                SOUND_CONFIG_VERSION + 1
+            ),
+        )
+        .unwrap();
+
+        let loaded = load_sound_config(&path).unwrap().unwrap();
+
+        assert_eq!(loaded.music_volume_decibels, -3.0);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn non_finite_volume_is_an_error() {
+        let contents =
+            format!("(version: {SOUND_CONFIG_VERSION}, config: (music_volume_decibels: NaN))");
+
+        let loaded = SoundConfigFile::migrate(&contents, SOUND_CONFIG_VERSION);
+
+        assert!(matches!(
+            loaded,
+            Err(SoundConfigPersistenceError::Invalid {
+                field: "music_volume_decibels",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn inverted_volume_range_is_an_error() {
+        let contents = format!(
+            "(version: {SOUND_CONFIG_VERSION}, config: (volume_range: (min_decibels: 0.0, max_decibels: -60.0)))"
+        );
+
+        let loaded = SoundConfigFile::migrate(&contents, SOUND_CONFIG_VERSION);
+
+        assert!(matches!(loaded, Err(SoundConfigPersistenceError::Parse(_))));
+    }
+
+    #[test]
+    fn invalid_file_falls_back_to_defaults() {
+        let directory = test_directory("invalid_fallback");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join(SOUND_CONFIG_FILE_NAME),
+            format!("(version: {SOUND_CONFIG_VERSION}, config: (global_volume_decibels: -inf))"),
+        )
+        .unwrap();
+
+        let mut app = App::new();
+        app.add_plugins(SoundConfigPersistencePlugin {
+            directory: directory.clone(),
+            save_debounce: DEFAULT_SAVE_DEBOUNCE,
+        });
+
+        let sound_config = app.world().resource::<SoundConfig>();
+        assert_eq!(
+            sound_config.global_volume_decibels,
+            SoundConfig::default().global_volume_decibels
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
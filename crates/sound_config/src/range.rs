@@ -0,0 +1,314 @@
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use derive_more::derive::{Display, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::Decibels;
+
+/// The highest volume slider position.
+pub const MAX_SLIDER_POSITION: f32 = 100.0;
+
+/// The range the volumes of a [`SoundConfig`](crate::SoundConfig) are clamped
+/// to.
+///
+/// The bottom of the range is true silence rather than a very quiet volume.
+///
+/// The bounds are validated when the range is created or deserialized, so a
+/// hand-edited sound config cannot make clamping panic.
+#[derive(Clone, Copy, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(
+    feature = "reflect",
+    derive(Reflect),
+    reflect(Default, Deserialize, PartialEq, Serialize)
+)]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+#[serde(try_from = "VolumeRangeBounds", into = "VolumeRangeBounds")]
+pub struct VolumeRange {
+    min_decibels: f32,
+    max_decibels: f32,
+}
+
+/// The bounds of a [`VolumeRange`] were not finite, or were not in order.
+#[derive(Clone, Copy, Debug, Display, Error, PartialEq)]
+#[display(
+    "invalid volume range from {min_decibels} to {max_decibels} decibels, the bounds must be finite and in order"
+)]
+pub struct InvalidVolumeRange {
+    pub min_decibels: f32,
+    pub max_decibels: f32,
+}
+
+/// The persisted form of a [`VolumeRange`], validated when it is read.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+struct VolumeRangeBounds {
+    min_decibels: f32,
+    max_decibels: f32,
+}
+
+impl TryFrom<VolumeRangeBounds> for VolumeRange {
+    type Error = InvalidVolumeRange;
+
+    fn try_from(bounds: VolumeRangeBounds) -> Result<Self, Self::Error> {
+        VolumeRange::new(bounds.min_decibels, bounds.max_decibels)
+    }
+}
+
+impl From<VolumeRange> for VolumeRangeBounds {
+    fn from(range: VolumeRange) -> Self {
+        VolumeRangeBounds {
+            min_decibels: range.min_decibels,
+            max_decibels: range.max_decibels,
+        }
+    }
+}
+
+impl Default for VolumeRange {
+    fn default() -> Self {
+        VolumeRange {
+            min_decibels: Decibels::SILENCE.0,
+            max_decibels: Decibels::IDENTITY.0,
+        }
+    }
+}
+
+impl VolumeRange {
+    /// How many tenfold steps of slider position the range is spread over.
+    ///
+    /// The slider only has two, from 1 to [`MAX_SLIDER_POSITION`], so the
+    /// lowest audible position stays a third of the range above silence.
+    const CURVE_DECADES: f32 = 3.0;
+
+    /// Creates a range from its bounds, which must be finite with the minimum
+    /// not above the maximum.
+    pub fn new(min_decibels: f32, max_decibels: f32) -> Result<Self, InvalidVolumeRange> {
+        if min_decibels.is_finite() && max_decibels.is_finite() && min_decibels <= max_decibels {
+            Ok(VolumeRange {
+                min_decibels,
+                max_decibels,
+            })
+        } else {
+            Err(InvalidVolumeRange {
+                min_decibels,
+                max_decibels,
+            })
+        }
+    }
+
+    /// The bottom of the range, which is silent.
+    #[inline(always)]
+    pub fn min_decibels(&self) -> f32 {
+        self.min_decibels
+    }
+
+    /// The top of the range, which is the loudest volume a slider reaches.
+    #[inline(always)]
+    pub fn max_decibels(&self) -> f32 {
+        self.max_decibels
+    }
+
+    #[inline(always)]
+    pub fn clamp(&self, decibels: f32) -> f32 {
+        decibels.clamp(self.min_decibels, self.max_decibels)
+    }
+
+    /// Returns `true` if the volume is at or below the bottom of the range.
+    #[inline(always)]
+    pub fn is_silent(&self, decibels: f32) -> bool {
+        decibels <= self.min_decibels
+    }
+
+    /// How many decibels the volume drops each time the slider position is
+    /// divided by ten.
+    #[inline(always)]
+    fn decibels_per_decade(&self) -> f32 {
+        (self.max_decibels - self.min_decibels) / Self::CURVE_DECADES
+    }
+
+    /// Maps a slider position from 0 to [`MAX_SLIDER_POSITION`] to a volume
+    /// in the range.
+    ///
+    /// The slider follows a perceptual curve rather than a linear one in
+    /// decibels, so that each step sounds about equally loud. The curve is
+    /// derived from the range so that only position 0 is silent.
+    pub fn decibels_from_slider(&self, position: f32) -> f32 {
+        let position = position.clamp(0.0, MAX_SLIDER_POSITION) / MAX_SLIDER_POSITION;
+        if position <= 0.0 {
+            return self.min_decibels;
+        }
+
+        self.clamp(self.max_decibels + self.decibels_per_decade() * position.log10())
+    }
+
+    /// Maps a volume to a slider position from 0 to [`MAX_SLIDER_POSITION`],
+    /// the inverse of [`Self::decibels_from_slider`].
+    pub fn slider_from_decibels(&self, decibels: f32) -> f32 {
+        if self.is_silent(decibels) {
+            return 0.0;
+        }
+
+        let decibels_per_decade = self.decibels_per_decade();
+        if decibels_per_decade <= 0.0 {
+            // An empty range is either silent or at its top.
+            return MAX_SLIDER_POSITION;
+        }
+
+        let position = 10f32.powf((self.clamp(decibels) - self.max_decibels) / decibels_per_decade);
+        (position * MAX_SLIDER_POSITION).clamp(0.0, MAX_SLIDER_POSITION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RANGES: [VolumeRange; 3] = [
+        VolumeRange {
+            min_decibels: -60.0,
+            max_decibels: 0.0,
+        },
+        VolumeRange {
+            min_decibels: -80.0,
+            max_decibels: 6.0,
+        },
+        VolumeRange {
+            min_decibels: -30.0,
+            max_decibels: -10.0,
+        },
+    ];
+
+    #[test]
+    fn only_position_zero_is_silent() {
+        for range in RANGES {
+            assert!(range.is_silent(range.decibels_from_slider(0.0)));
+            for position in 1..=MAX_SLIDER_POSITION as u32 {
+                let decibels = range.decibels_from_slider(position as f32);
+                assert!(!range.is_silent(decibels), "{position} is silent");
+            }
+        }
+    }
+
+    #[test]
+    fn ends_of_slider_are_ends_of_range() {
+        for range in RANGES {
+            assert_eq!(range.decibels_from_slider(0.0), range.min_decibels);
+            assert_eq!(
+                range.decibels_from_slider(MAX_SLIDER_POSITION),
+                range.max_decibels
+            );
+            assert_eq!(range.slider_from_decibels(range.min_decibels), 0.0);
+            assert_eq!(
+                range.slider_from_decibels(range.max_decibels),
+                MAX_SLIDER_POSITION
+            );
+        }
+    }
+
+    #[test]
+    fn default_range_curve() {
+        let range = VolumeRange::default();
+
+        assert!((range.decibels_from_slider(1.0) - -40.0).abs() < 1e-4);
+        assert!((range.decibels_from_slider(10.0) - -20.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn slider_round_trip() {
+        for range in RANGES {
+            for position in 0..=MAX_SLIDER_POSITION as u32 {
+                let position = position as f32;
+                let round_trip = range.slider_from_decibels(range.decibels_from_slider(position));
+                assert!(
+                    (round_trip - position).abs() < 1e-3,
+                    "{position} became {round_trip}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decibels_round_trip() {
+        for range in RANGES {
+            let mut decibels = range.min_decibels;
+            while decibels <= range.max_decibels {
+                let round_trip = range.decibels_from_slider(range.slider_from_decibels(decibels));
+                assert!(
+                    (round_trip - decibels).abs() < 1e-3,
+                    "{decibels} became {round_trip}"
+                );
+                decibels += 0.5;
+            }
+        }
+    }
+
+    #[test]
+    fn slider_is_monotonic() {
+        for range in RANGES {
+            let mut previous = range.decibels_from_slider(0.0);
+            for position in 1..=MAX_SLIDER_POSITION as u32 {
+                let decibels = range.decibels_from_slider(position as f32);
+                assert!(decibels > previous);
+                previous = decibels;
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_input_is_clamped() {
+        let range = VolumeRange::default();
+
+        assert_eq!(range.decibels_from_slider(-5.0), range.min_decibels);
+        assert_eq!(
+            range.decibels_from_slider(MAX_SLIDER_POSITION * 2.0),
+            range.max_decibels
+        );
+        assert_eq!(range.slider_from_decibels(f32::NEG_INFINITY), 0.0);
+        assert_eq!(range.slider_from_decibels(12.0), MAX_SLIDER_POSITION);
+    }
+
+    #[test]
+    fn empty_range() {
+        let range = VolumeRange {
+            min_decibels: -6.0,
+            max_decibels: -6.0,
+        };
+
+        assert_eq!(range.decibels_from_slider(0.0), -6.0);
+        assert_eq!(range.decibels_from_slider(50.0), -6.0);
+        assert_eq!(range.slider_from_decibels(-6.0), 0.0);
+    }
+
+    #[test]
+    fn inverted_range_is_invalid() {
+        assert!(
+            VolumeRange::new(0.0, -60.0)
+                == Err(InvalidVolumeRange {
+                    min_decibels: 0.0,
+                    max_decibels: -60.0,
+                })
+        );
+        assert!(ron::from_str::<VolumeRange>("(min_decibels: 0.0, max_decibels: -60.0)").is_err());
+    }
+
+    #[test]
+    fn non_finite_range_is_invalid() {
+        for (min_decibels, max_decibels) in [
+            (f32::NAN, 0.0),
+            (-60.0, f32::NAN),
+            (f32::NEG_INFINITY, 0.0),
+            (-60.0, f32::INFINITY),
+        ] {
+            assert!(VolumeRange::new(min_decibels, max_decibels).is_err());
+        }
+        assert!(ron::from_str::<VolumeRange>("(min_decibels: NaN, max_decibels: 0.0)").is_err());
+    }
+
+    #[test]
+    fn valid_range_round_trips_through_ron() {
+        let range = VolumeRange::new(-40.0, 6.0).unwrap();
+
+        let ron = ron::to_string(&range).unwrap();
+
+        assert!(ron::from_str::<VolumeRange>(&ron).unwrap() == range);
+    }
+}
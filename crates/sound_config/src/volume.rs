@@ -0,0 +1,133 @@
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A volume in decibels that does not depend on an audio backend.
+///
+/// Enable the `kira` or `seedling` feature to convert it to the volume type of
+/// that backend.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, PartialOrd, Serialize)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(
+    feature = "reflect",
+    derive(Reflect),
+    reflect(Default, Deserialize, PartialEq, Serialize)
+)]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct Decibels(pub f32);
+
+impl Decibels {
+    /// The volume at which a sound is considered silent. Anything at or below
+    /// this volume is silent.
+    pub const SILENCE: Self = Self(-60.0);
+    /// The volume that leaves a sound unchanged.
+    pub const IDENTITY: Self = Self(0.0);
+
+    /// Returns `true` if the volume is at or below [`Self::SILENCE`].
+    #[inline(always)]
+    pub fn is_silent(&self) -> bool {
+        self.0 <= Self::SILENCE.0
+    }
+}
+
+impl From<f32> for Decibels {
+    #[inline(always)]
+    fn from(decibels: f32) -> Self {
+        Decibels(decibels)
+    }
+}
+
+impl From<Decibels> for f32 {
+    #[inline(always)]
+    fn from(decibels: Decibels) -> Self {
+        decibels.0
+    }
+}
+
+#[cfg(feature = "kira")]
+impl From<Decibels> for bevy_kira_audio::prelude::Decibels {
+    #[inline(always)]
+    fn from(decibels: Decibels) -> Self {
+        bevy_kira_audio::prelude::Decibels(decibels.0)
+    }
+}
+
+#[cfg(feature = "kira")]
+impl From<bevy_kira_audio::prelude::Decibels> for Decibels {
+    #[inline(always)]
+    fn from(decibels: bevy_kira_audio::prelude::Decibels) -> Self {
+        Decibels(decibels.0)
+    }
+}
+
+/// Converts as is, so that a volume survives a round trip through seedling.
+/// Silence is up to the [`SoundConfig`](crate::SoundConfig), whose volumes are
+/// negative infinity when muted or at the bottom of their range.
+#[cfg(feature = "seedling")]
+impl From<Decibels> for bevy_seedling::prelude::Volume {
+    #[inline(always)]
+    fn from(decibels: Decibels) -> Self {
+        bevy_seedling::prelude::Volume::Decibels(decibels.0)
+    }
+}
+
+/// Converts as is, so a silent volume becomes negative infinity.
+#[cfg(feature = "seedling")]
+impl From<bevy_seedling::prelude::Volume> for Decibels {
+    #[inline(always)]
+    fn from(volume: bevy_seedling::prelude::Volume) -> Self {
+        Decibels(volume.decibels())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VOLUMES: [f32; 6] = [f32::NEG_INFINITY, -80.0, -60.0, -6.5, 0.0, 6.0];
+
+    #[test]
+    fn f32_round_trip() {
+        for decibels in VOLUMES {
+            assert_eq!(f32::from(Decibels::from(decibels)), decibels);
+        }
+    }
+
+    #[test]
+    fn silence() {
+        assert!(Decibels(f32::NEG_INFINITY).is_silent());
+        assert!(Decibels::SILENCE.is_silent());
+        assert!(!Decibels(-59.9).is_silent());
+        assert!(!Decibels::IDENTITY.is_silent());
+    }
+
+    #[cfg(feature = "seedling")]
+    #[test]
+    fn seedling_round_trip() {
+        use bevy_seedling::prelude::Volume;
+
+        for decibels in VOLUMES {
+            let volume = Volume::from(Decibels(decibels));
+            assert_eq!(volume.decibels(), decibels);
+            assert_eq!(Decibels::from(volume).0, decibels);
+        }
+    }
+
+    #[cfg(feature = "seedling")]
+    #[test]
+    fn seedling_silence() {
+        use bevy_seedling::prelude::Volume;
+
+        assert_eq!(Volume::from(Decibels(f32::NEG_INFINITY)).linear(), 0.0);
+        assert_eq!(Decibels::from(Volume::SILENT).0, f32::NEG_INFINITY);
+    }
+
+    #[cfg(feature = "kira")]
+    #[test]
+    fn kira_round_trip() {
+        for decibels in VOLUMES {
+            let kira_decibels = bevy_kira_audio::prelude::Decibels::from(Decibels(decibels));
+            assert_eq!(Decibels::from(kira_decibels).0, decibels);
+        }
+    }
+}
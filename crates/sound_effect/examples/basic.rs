@@ -13,21 +13,28 @@ use dev_tools::prelude::*;
 use sound_config::prelude::*;
 use sound_effect::prelude::*;
 
-fn spatial_bundle(
+fn spawn_sound_effect(
+    commands: &mut Commands,
+    args: &Args,
     mesh_assets: &mut ResMut<Assets<Mesh>>,
     standard_materials: &mut ResMut<Assets<StandardMaterial>>,
-    packet: &Handle<PacketAsset>,
-    id: u8,
-) -> impl Bundle {
-    (
-        SpatialSoundEffect::new(packet.clone(), id),
-        Transform::from_xyz(0.0, 0.0, 0.0),
-        Mesh3d(mesh_assets.add(Cuboid::new(1.0, 1.0, 1.0))),
-        MeshMaterial3d(standard_materials.add(StandardMaterial {
-            base_color: TEAL_400.into(),
-            ..Default::default()
-        })),
-    )
+) {
+    let mut entity = commands.spawn(
+        SoundEffect::from_key(&SoundEffectKey::custom(args.packet_path.clone(), args.id))
+            .with_spatial(args.spatial),
+    );
+
+    // Give spatial sound effects something to look at.
+    if args.spatial {
+        entity.insert((
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            Mesh3d(mesh_assets.add(Cuboid::new(1.0, 1.0, 1.0))),
+            MeshMaterial3d(standard_materials.add(StandardMaterial {
+                base_color: TEAL_400.into(),
+                ..Default::default()
+            })),
+        ));
+    }
 }
 
 #[derive(InputAction)]
@@ -38,29 +45,20 @@ fn on_play(
     _: On<Start<Play>>,
     mut commands: Commands,
     args: Res<Args>,
-    asset_server: Res<AssetServer>,
     mut mesh_assets: ResMut<Assets<Mesh>>,
     mut standard_materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    if args.spatial {
-        commands.spawn(spatial_bundle(
-            &mut mesh_assets,
-            &mut standard_materials,
-            &asset_server.load(&args.packet_path),
-            args.id,
-        ));
-    } else {
-        commands.spawn(SoundEffectPlayer::new(SoundEffectKey::custom(
-            args.packet_path.clone(),
-            args.id,
-        )));
-    }
+    spawn_sound_effect(
+        &mut commands,
+        &args,
+        &mut mesh_assets,
+        &mut standard_materials,
+    );
 }
 
 fn maybe_auto_play(
     mut commands: Commands,
     args: Res<Args>,
-    asset_server: Res<AssetServer>,
     mut mesh_assets: ResMut<Assets<Mesh>>,
     mut standard_materials: ResMut<Assets<StandardMaterial>>,
 ) {
@@ -68,19 +66,12 @@ fn maybe_auto_play(
         return;
     }
 
-    if args.spatial {
-        commands.spawn(spatial_bundle(
-            &mut mesh_assets,
-            &mut standard_materials,
-            &asset_server.load(&args.packet_path),
-            args.id,
-        ));
-    } else {
-        commands.spawn(SoundEffectPlayer::new(SoundEffectKey::custom(
-            args.packet_path.clone(),
-            args.id,
-        )));
-    }
+    spawn_sound_effect(
+        &mut commands,
+        &args,
+        &mut mesh_assets,
+        &mut standard_materials,
+    );
 }
 
 #[derive(Component, Reflect)]
@@ -172,12 +163,10 @@ struct Args {
     #[arg(long, default_value_t = false)]
     spatial: bool,
 
-    /// Whether to not use the deferred approach. If true, uses the observer
+    /// Whether to use the deferred approach. If false, uses the observer
     /// approach which is the one with the bug spawning many nodes.
-    ///
-    /// TODO: Not implemented yet.
-    #[arg(long, default_value_t = true)]
-    no_deferred: bool,
+    #[arg(long, default_value_t = false)]
+    deferred: bool,
 }
 
 fn main() {
@@ -220,7 +209,7 @@ fn main() {
         move |args: Res<Args>, mut config: ResMut<SoundConfig>, mut mode: ResMut<Mode>| {
             config.global_volume_decibels = args.global_volume_decibels;
 
-            mode.deferred = !args.no_deferred;
+            mode.deferred = args.deferred;
         },
     );
 
@@ -1,4 +1,7 @@
-use std::path::PathBuf;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 use bevy_app::prelude::*;
 use bevy_app_ext::prelude::*;
@@ -12,6 +15,10 @@ use derive_more::derive::{Display, Error, From};
 use serde::{Deserialize, Serialize};
 
 use darkomen::{asset::paths::*, sound::sfx::*};
+#[allow(unused_imports)]
+use tracing::*;
+
+use crate::{RandomLoopingGap, SoundSelection};
 
 pub struct SoundEffectAssetPlugin;
 
@@ -27,6 +34,7 @@ impl Plugin for SoundEffectAssetPlugin {
         {
             app.register_asset_reflect::<PacketAsset>();
             app.register_type::<PacketAssetHandle>();
+            app.register_type::<SoundEffectFlags>();
         }
     }
 }
@@ -39,6 +47,8 @@ impl Plugin for SoundEffectAssetPlugin {
 pub struct PacketAsset {
     source: Packet,
     audio_samples: HashMap<String, Handle<AudioSample>>,
+    /// Overrides from the packet's sidecar file, by sound effect ID.
+    overrides: BTreeMap<SfxId, SoundEffectOverride>,
 }
 
 /// A [`Handle`] to a [`PacketAsset`] asset.
@@ -73,6 +83,14 @@ impl PacketAsset {
         self.source.sfxs.get(&sfx_id)
     }
 
+    pub fn sound_effect_override(&self, sfx_id: SfxId) -> Option<&SoundEffectOverride> {
+        self.overrides.get(&sfx_id)
+    }
+
+    pub fn sound_effect_flags(&self, sfx_id: SfxId) -> Option<SoundEffectFlags> {
+        self.sound_effect(sfx_id).map(SoundEffectFlags::from_sfx)
+    }
+
     pub fn sound_effect_sound(&self, sound_effect: &Sfx, sound_index: usize) -> Option<Sound> {
         sound_effect.sounds.get(sound_index).cloned()
     }
@@ -88,6 +106,130 @@ impl PacketAsset {
     }
 }
 
+/// The raw `FLAGS` of a sound effect in a packet.
+///
+/// What the bits mean is not known: the only packet we have to go by sets
+/// `FLAGS: 2` on every sound effect, whatever its type or priority. The flags
+/// are exposed as read, and do not change how the sound effect is played.
+#[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(
+    feature = "reflect",
+    derive(Reflect),
+    reflect(Default, Hash, PartialEq)
+)]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct SoundEffectFlags(u32);
+
+impl SoundEffectFlags {
+    /// No flags are set.
+    pub const NONE: Self = Self(0);
+
+    /// Creates flags from the raw bits.
+    #[inline(always)]
+    pub const fn from_bits_retain(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Reads the flags of the given sound effect.
+    #[inline(always)]
+    pub fn from_sfx(sound_effect: &Sfx) -> Self {
+        Self::from_bits_retain(sound_effect.flags as u32)
+    }
+
+    #[inline(always)]
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Overrides for a sound effect of a packet, read from the packet's sidecar
+/// file.
+///
+/// The sidecar file sits next to the packet with a `.ron` extension added,
+/// e.g., `DARKOMEN/SOUND/H/BIRDS.H.ron`, and maps sound effect IDs to their
+/// overrides:
+///
+/// ```ron
+/// (
+///     sound_effects: {
+///         0: (
+///             selection: Some(ShuffleBag),
+///             weights: {"bird3": 0.5},
+///             gap: Some((min: 2.0, max: 6.0)),
+///         ),
+///     },
+/// )
+/// ```
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(
+    feature = "reflect",
+    derive(Reflect),
+    reflect(Default, Deserialize, Serialize)
+)]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+#[serde(default)]
+pub struct SoundEffectOverride {
+    /// How the sounds are picked, unless the emitter chooses.
+    pub selection: Option<SoundSelection>,
+    /// The weight of each sound by file stem. Sounds that are not listed have
+    /// a weight of 1. Weights must be finite and not negative, others are
+    /// ignored when the sidecar file is read.
+    pub weights: BTreeMap<String, f32>,
+    /// The silence between the sounds of a random looping sound effect, unless
+    /// the emitter chooses. See [`RandomLoopingGap`].
+    pub gap: Option<RandomLoopingGap>,
+}
+
+impl SoundEffectOverride {
+    #[inline(always)]
+    pub fn weight(&self, file_stem: &str) -> f32 {
+        self.weights.get(file_stem).copied().unwrap_or(1.0)
+    }
+
+    /// Drops the weights that are not finite or are negative, which cannot be
+    /// picked from, and returns the file stems they were given for.
+    fn remove_invalid_weights(&mut self) -> Vec<String> {
+        let mut removed = Vec::new();
+        self.weights.retain(|file_stem, weight| {
+            let valid = weight.is_finite() && *weight >= 0.0;
+            if !valid {
+                removed.push(file_stem.clone());
+            }
+            valid
+        });
+        removed
+    }
+}
+
+/// The contents of a packet's sidecar file.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct PacketOverrides {
+    sound_effects: BTreeMap<SfxId, SoundEffectOverride>,
+}
+
+impl PacketOverrides {
+    /// Parses a sidecar file, dropping the values that cannot be used.
+    fn from_bytes(path: &Path, bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+        let mut overrides = ron::de::from_bytes::<PacketOverrides>(bytes)?;
+
+        for (sound_effect_id, sound_effect_override) in &mut overrides.sound_effects {
+            for file_stem in sound_effect_override.remove_invalid_weights() {
+                warn!(
+                    ?path,
+                    sound_effect_id,
+                    %file_stem,
+                    "Ignoring sound weight that is not a finite, positive number"
+                );
+            }
+        }
+
+        Ok(overrides)
+    }
+}
+
 #[derive(Clone)]
 pub struct PacketAssetLoader {
     asset_paths: AssetPaths,
@@ -173,9 +315,12 @@ impl AssetLoader for PacketAssetLoader {
             );
         }
 
+        let overrides = load_packet_overrides(load_context).await;
+
         Ok(PacketAsset {
             source: packet,
             audio_samples,
+            overrides,
         })
     }
 
@@ -193,3 +338,92 @@ impl FromWorld for PacketAssetLoader {
         }
     }
 }
+
+/// Reads the packet's sidecar file, if any.
+///
+/// A missing sidecar file is the norm, and a broken one should not stop the
+/// packet itself from playing, so both are treated as having no overrides.
+async fn load_packet_overrides(
+    load_context: &mut LoadContext<'_>,
+) -> BTreeMap<SfxId, SoundEffectOverride> {
+    let mut path = load_context.path().as_os_str().to_owned();
+    path.push(".ron");
+    let path = PathBuf::from(path);
+
+    let Ok(bytes) = load_context.read_asset_bytes(path.as_path()).await else {
+        return BTreeMap::default();
+    };
+
+    match PacketOverrides::from_bytes(&path, &bytes) {
+        Ok(overrides) => overrides.sound_effects,
+        Err(error) => {
+            warn!(?path, %error, "Could not parse packet sidecar file, ignoring it");
+            BTreeMap::default()
+        }
+    }
+}
+
+#[cfg(test)]
+impl PacketAsset {
+    /// Decodes the `BIRDS.H` packet of the example assets, without loading
+    /// its audio samples or a sidecar file.
+    pub(crate) fn birds() -> Self {
+        let bytes = include_bytes!("../assets/DARKOMEN/SOUND/H/BIRDS.H");
+        let source = Decoder::new(std::io::Cursor::new(bytes.to_vec()))
+            .decode()
+            .unwrap();
+
+        PacketAsset {
+            source,
+            audio_samples: HashMap::default(),
+            overrides: BTreeMap::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_file() {
+        let overrides = PacketOverrides::from_bytes(
+            Path::new("BIRDS.H.ron"),
+            br#"(
+                sound_effects: {
+                    2: (
+                        selection: Some(AvoidLast(2)),
+                        weights: {"bird1": 0.5, "bird2": 0.0},
+                        gap: Some((min: 2.0, max: 6.0)),
+                    ),
+                },
+            )"#,
+        )
+        .unwrap();
+
+        let sound_effect_override = &overrides.sound_effects[&2];
+        assert!(sound_effect_override.selection == Some(SoundSelection::AvoidLast(2)));
+        assert_eq!(sound_effect_override.weight("bird1"), 0.5);
+        assert_eq!(sound_effect_override.weight("bird2"), 0.0);
+        assert_eq!(sound_effect_override.weight("bird3"), 1.0);
+    }
+
+    #[test]
+    fn sidecar_file_drops_invalid_weights() {
+        let overrides = PacketOverrides::from_bytes(
+            Path::new("BIRDS.H.ron"),
+            br#"(
+                sound_effects: {
+                    0: (
+                        weights: {"bird1": inf, "bird2": NaN, "bird3": -1.0, "bird4": 2.0},
+                    ),
+                },
+            )"#,
+        )
+        .unwrap();
+
+        let weights = &overrides.sound_effects[&0].weights;
+        assert_eq!(weights.len(), 1);
+        assert_eq!(weights["bird4"], 2.0);
+    }
+}
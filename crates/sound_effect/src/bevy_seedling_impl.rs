@@ -1,121 +1,227 @@
-use core::marker::PhantomData;
+mod bus;
+mod control;
+mod doppler;
+mod envelope;
+mod failure;
+mod rng;
+mod schedule;
+mod spatial;
+mod virtualization;
+mod voice;
+
+use core::{marker::PhantomData, time::Duration};
 
 use bevy_app::prelude::*;
 use bevy_app_ext::prelude::*;
-use bevy_asset::prelude::*;
+use bevy_asset::{LoadState, prelude::*};
 use bevy_ecs::prelude::*;
 use bevy_rand::prelude::*;
 #[cfg(feature = "reflect")]
 use bevy_reflect::prelude::*;
 use bevy_seedling::{
-    context::SampleRate,
-    firewheel::{
-        dsp::distance_attenuation::{DistanceAttenuation, DistanceModel},
-        nodes::spatial_basic::SpatialBasicNode,
-    },
+    SeedlingSystems, context::SampleRate, firewheel::nodes::spatial_basic::SpatialBasicNode,
     prelude::*,
-    sample_effects,
 };
 use bevy_time::prelude::*;
 use bevy_transform::prelude::*;
-use darkomen::prelude::*;
+use darkomen::{prelude::*, sound::sfx::SfxId};
 use rand::Rng;
 use sound_config::prelude::*;
 #[allow(unused_imports)]
 use tracing::*;
 
+use self::{
+    bus::BusPlugin,
+    control::ControlPlugin,
+    doppler::{DopplerBaseSpeed, DopplerPlugin},
+    envelope::{EnvelopePlugin, SoundEnvelope},
+    failure::{PlaybackFailurePlugin, report_playback_failure},
+    rng::{RngPlugin, emitter_rng},
+    schedule::{NextSoundSchedule, SchedulePlugin, release_scheduled_next_sounds},
+    spatial::SpatialPlugin,
+    virtualization::{Earshot, SoundDurations, VirtualizationPlugin},
+    voice::{StolenVoice, VOICE_RETRY_DELAY, Voice, VoicePlugin, Voices},
+};
+pub use self::{
+    bus::{SoundCategoryBus, SoundCategoryPool},
+    control::{PauseSoundEffect, ResumeSoundEffect, SoundEffectCommandsExt, SoundEffectPaused},
+    doppler::{DopplerSettings, DopplerVelocity},
+    envelope::{SoundEffectStopped, StopSoundEffect},
+    failure::{
+        FailedSoundEffect, PlaybackFailurePolicy, SoundEffectPlaybackError,
+        SoundEffectPlaybackFailed,
+    },
+    rng::SoundEffectRng,
+    spatial::SpatialSoundEffectSettings,
+    virtualization::{VirtualSoundEffect, VirtualizationSettings},
+    voice::DEFAULT_MAX_VOICES,
+};
 use crate::{
-    PacketAssetHandle, RandomLoopingSoundEffect, SoundEffectId, SoundEffectKeyRequirements,
-    SoundEffectPacketAssetCollection, SpatialSoundEffect, asset::*, sound_extension::SoundExt as _,
+    LoadedSoundEffectPackets, PacketAssetHandle, RandomLoopingGap, RandomLoopingSoundEffect,
+    SequentialSoundEffect, SoundEffect, SoundEffectId, SoundEffectKeyRequirements,
+    SoundEffectPacketAssetCollection, SoundEffectSource, SoundSelection, SpatialSoundEffect,
+    asset::*,
+    selection::{SoundSelectionState, select_sound},
+    sound_extension::SoundExt as _,
 };
 
-pub(super) struct SoundEffectPlugin<SoundEffectKeyT: SoundEffectKeyRequirements>(
-    PhantomData<SoundEffectKeyT>,
-);
-
-impl<SoundEffectKeyT: SoundEffectKeyRequirements> SoundEffectPlugin<SoundEffectKeyT> {
-    pub(super) fn new() -> Self {
-        SoundEffectPlugin(PhantomData)
-    }
+pub(super) struct SoundEffectPlugin<SoundEffectKeyT: SoundEffectKeyRequirements> {
+    pub(super) max_voices: usize,
+    pub(super) failure_policy: PlaybackFailurePolicy,
+    pub(super) packet_load_timeout: Duration,
+    pub(super) spatial_settings: SpatialSoundEffectSettings,
+    pub(super) doppler_settings: Option<DopplerSettings>,
+    pub(super) virtualization_settings: Option<VirtualizationSettings>,
+    pub(super) _marker: PhantomData<SoundEffectKeyT>,
 }
 
 impl<SoundEffectKeyT: SoundEffectKeyRequirements> Plugin for SoundEffectPlugin<SoundEffectKeyT> {
     fn build(&self, app: &mut App) {
         app.try_add_plugins(EntropyPlugin::<WyRand>::default());
         app.try_add_plugins(SeedlingPlugin::default());
+        app.try_add_plugins(EnvelopePlugin);
+        app.try_add_plugins(ControlPlugin);
+        app.try_add_plugins(RngPlugin);
+        app.try_add_plugins(SchedulePlugin);
+        app.try_add_plugins(BusPlugin);
+        app.try_add_plugins(SpatialPlugin {
+            settings: self.spatial_settings.clone(),
+        });
+        app.try_add_plugins(DopplerPlugin {
+            settings: self.doppler_settings.clone(),
+        });
+        app.try_add_plugins(VirtualizationPlugin {
+            settings: self.virtualization_settings.clone(),
+        });
+        app.try_add_plugins(VoicePlugin {
+            max_voices: self.max_voices,
+        });
+        app.try_add_plugins(PlaybackFailurePlugin {
+            policy: self.failure_policy,
+        });
 
+        app.insert_resource(PacketLoadTimeout(self.packet_load_timeout));
         app.init_resource::<Mode>();
-        app.init_resource::<SpatialSoundEffectSettings>();
 
         #[cfg(feature = "reflect")]
         {
             app.register_type::<Mode>();
-            app.register_type::<SpatialSoundEffectSettings>();
+            app.register_type::<PacketLoadTimeout>();
 
             app.register_type::<SoundEffectPlayer<SoundEffectKeyT>>();
             app.register_type::<RandomLoopingSoundPlayerMarker>();
+            app.register_type::<SequentialSoundPlayerMarker>();
+            app.register_type::<PendingSoundEffect>();
+            app.register_type::<PendingNextSound>();
+            app.register_type::<IdleSoundPlayer>();
+            app.register_type::<AwaitingPacket>();
         }
 
         app.add_observer(on_sound_effect_player_added::<SoundEffectKeyT>);
-        app.add_observer(on_random_looping_sound_player_removed);
         app.add_observer(on_spatial_sound_effect_added);
+        app.add_observer(on_sound_effect_added);
+        app.add_observer(on_sound_player_finished);
+        app.add_observer(on_voice_stolen);
+
+        app.configure_sets(
+            Last,
+            SoundEffectSystems::Play.before(SeedlingSystems::Acquire),
+        );
+        app.add_systems(
+            Last,
+            (
+                play_awaiting_sound_effects,
+                play_pending_sound_effects,
+                release_scheduled_next_sounds.before(play_pending_next_sounds),
+                play_pending_next_sounds,
+            )
+                .in_set(SoundEffectSystems::Play),
+        );
 
         app.add_systems(Update, count_nodes);
     }
 }
 
+/// How sound effects are started.
 #[derive(Clone, Default, Resource)]
 #[cfg_attr(feature = "debug", derive(Debug))]
 #[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Resource))]
 #[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
 pub struct Mode {
+    /// If `true`, sound effects and their next sounds are started by systems
+    /// in [`SoundEffectSystems::Play`] rather than inside observers, so their
+    /// audio nodes are spawned in a predictable place in the schedule.
+    ///
+    /// Can be changed at any time. Requests already waiting are still played.
     pub deferred: bool,
 }
 
-/// Settings for spatial sound effects.
-///
-/// This resource allows configuring the [`SpatialBasicNode`] used for spatial
-/// audio effects.
-#[derive(Clone, Resource)]
+/// System sets for sound effect playback.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, SystemSet)]
+pub enum SoundEffectSystems {
+    /// Starts the sound effects requested in [`Mode::deferred`]. Runs in
+    /// [`Last`] before seedling acquires sampler nodes.
+    Play,
+}
+
+/// Marker component for a sound effect that is waiting to be started in
+/// [`Mode::deferred`].
+#[derive(Component)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+struct PendingSoundEffect;
+
+/// The default time to wait for a sound effect's packet to load before giving
+/// up.
+pub const DEFAULT_PACKET_LOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a sound effect waits for its packet and audio samples to load
+/// before a [`SoundEffectPlaybackFailed`] is triggered.
+#[derive(Clone, Copy, Resource)]
 #[cfg_attr(feature = "debug", derive(Debug))]
 #[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
 #[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
-struct SpatialSoundEffectSettings {
-    /// Distance attenuation settings for spatial audio.
-    distance_attenuation: DistanceAttenuation,
-}
+pub struct PacketLoadTimeout(pub Duration);
 
-impl Default for SpatialSoundEffectSettings {
+impl Default for PacketLoadTimeout {
     fn default() -> Self {
-        Self {
-            distance_attenuation: DistanceAttenuation {
-                distance_model: DistanceModel::Linear,
-                reference_distance: 2.0,
-                max_distance: 50.0,
-                ..DistanceAttenuation::default()
-            },
-        }
+        PacketLoadTimeout(DEFAULT_PACKET_LOAD_TIMEOUT)
     }
 }
 
-impl SpatialSoundEffectSettings {
-    /// Creates a new [`SpatialBasicNode`] using these settings.
-    fn create_node(&self) -> SpatialBasicNode {
-        SpatialBasicNode {
-            distance_attenuation: self.distance_attenuation,
-            ..SpatialBasicNode::default()
-        }
-    }
+/// Marks a sound effect that is waiting for its packet to load.
+#[derive(Component)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+struct AwaitingPacket {
+    /// The elapsed time when the sound effect was first requested.
+    since: Duration,
 }
 
+/// Marker component for a random looping or sequential sound effect that is
+/// waiting to play its next sound in [`Mode::deferred`].
+#[derive(Component)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+struct PendingNextSound;
+
+/// The sound player child of a random looping or sequential sound effect
+/// whose sound finished, kept with its effects so that the next sound reuses
+/// them instead of spawning new nodes.
+#[derive(Component)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+struct IdleSoundPlayer(Entity);
+
 /// Spawn an entity with this component to play a sound effect specified by the
 /// given key.
 ///
-/// If you want to spawn a spatial sound effect, instead spawn an entity with
-/// [`SpatialSoundEffect`], or add it to your existing entity.
-///
-/// TODO: In the future we might try and consolidate these two so callers just
-/// have to do one and then indicate if they want spatial or not.
+/// This is shorthand for [`SoundEffect::from_key`], which also lets you make the
+/// sound effect spatial.
 #[derive(Component)]
 #[cfg_attr(feature = "debug", derive(Debug))]
 #[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
@@ -131,15 +237,9 @@ impl<K: SoundEffectKeyRequirements> SoundEffectPlayer<K> {
     }
 }
 
-#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
 fn on_sound_effect_player_added<SoundEffectKeyT>(
     add: On<Add, SoundEffectPlayer<SoundEffectKeyT>>,
     mut commands: Commands,
-    mut rng: Single<&mut WyRand, With<GlobalRng>>,
-    sample_rate: Res<SampleRate>,
-    sound_config: Res<SoundConfig>,
-    packet_assets: Res<Assets<PacketAsset>>,
-    packets: Res<SoundEffectPacketAssetCollection>,
     query: Query<&SoundEffectPlayer<SoundEffectKeyT>>,
 ) where
     SoundEffectKeyT: SoundEffectKeyRequirements,
@@ -149,42 +249,334 @@ fn on_sound_effect_player_added<SoundEffectKeyT>(
         return;
     };
 
-    let (packet_path, sound_effect_id) = (
-        player.key.get_packet_path(),
-        player.key.get_sound_effect_id(),
-    );
+    commands
+        .entity(add.entity)
+        .try_insert(SoundEffect::from_key(&player.key));
+}
 
-    let _span = info_span!("", packet_path, sound_effect_id).entered();
+fn on_spatial_sound_effect_added(
+    add: On<Add, SpatialSoundEffect>,
+    mut commands: Commands,
+    query: Query<&SpatialSoundEffect>,
+) {
+    let Ok(spatial_sound_effect) = query.get(add.entity) else {
+        error!("Spatial sound effect not found");
+        return;
+    };
 
-    debug!("Playing sound effect");
+    commands.entity(add.entity).try_insert(
+        SoundEffect::new(spatial_sound_effect.packet.clone(), spatial_sound_effect.id).spatial(),
+    );
+}
 
-    let Some(packet_handle) = packets.get(&packet_path) else {
-        error!("Packet does not exist");
+/// Starts the sound effect right away, or leaves it for
+/// [`play_pending_sound_effects`] in [`Mode::deferred`].
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn on_sound_effect_added(
+    add: On<Add, SoundEffect>,
+    mut commands: Commands,
+    mode: Res<Mode>,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    sample_rate: Res<SampleRate>,
+    spatial_settings: Res<SpatialSoundEffectSettings>,
+    mut voices: ResMut<Voices>,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    packet_assets: Res<Assets<PacketAsset>>,
+    packet_sets: Res<LoadedSoundEffectPackets>,
+    packets: Option<Res<SoundEffectPacketAssetCollection>>,
+    earshot: Earshot,
+    mut query: Query<
+        (
+            &SoundEffect,
+            Option<&mut SoundEffectRng>,
+            Option<&mut SoundSelectionState>,
+            Option<&SpatialSoundEffectSettings>,
+        ),
+        Without<SoundEffectStopped>,
+    >,
+) {
+    if !query.contains(add.entity) {
+        debug!("Sound effect already stopped");
         return;
-    };
-    let Some(packet) = packet_assets.get(packet_handle.id()) else {
-        error!("Packet asset not loaded");
+    }
+
+    if mode.deferred {
+        commands.entity(add.entity).try_insert(PendingSoundEffect);
         return;
-    };
-    let Some(sound_effect) = packet.sound_effect(sound_effect_id) else {
-        error!("Sound effect does not exist");
+    }
+
+    let Ok((request, request_rng, mut selection_state, emitter_spatial_settings)) =
+        query.get_mut(add.entity)
+    else {
+        error!("Sound effect not found");
         return;
     };
 
-    match sound_effect.typ {
-        SfxType::RandomLooping => play_random_looping_sound_effect(
+    start_sound_effect(
+        commands.reborrow(),
+        emitter_rng(request_rng, rng.as_mut()),
+        &sample_rate,
+        emitter_spatial_settings.unwrap_or(&spatial_settings),
+        &mut voices,
+        &asset_server,
+        &packet_assets,
+        &packet_sets,
+        packets.as_deref(),
+        &earshot,
+        time.elapsed(),
+        add.entity,
+        request,
+        selection_state.as_deref_mut(),
+    );
+}
+
+/// Starts the sound effects that were requested in [`Mode::deferred`].
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn play_pending_sound_effects(
+    mut commands: Commands,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    sample_rate: Res<SampleRate>,
+    spatial_settings: Res<SpatialSoundEffectSettings>,
+    mut voices: ResMut<Voices>,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    packet_assets: Res<Assets<PacketAsset>>,
+    packet_sets: Res<LoadedSoundEffectPackets>,
+    packets: Option<Res<SoundEffectPacketAssetCollection>>,
+    earshot: Earshot,
+    mut query: Query<
+        (
+            Entity,
+            &SoundEffect,
+            Option<&mut SoundEffectRng>,
+            Option<&mut SoundSelectionState>,
+            Option<&SpatialSoundEffectSettings>,
+        ),
+        (
+            With<PendingSoundEffect>,
+            Without<VirtualSoundEffect>,
+            Without<SoundEffectStopped>,
+        ),
+    >,
+) {
+    for (entity, request, request_rng, mut selection_state, emitter_spatial_settings) in &mut query
+    {
+        commands.entity(entity).remove::<PendingSoundEffect>();
+
+        start_sound_effect(
             commands.reborrow(),
-            &mut rng,
+            emitter_rng(request_rng, rng.as_mut()),
             &sample_rate,
-            &sound_config,
-            packet_handle,
-            packet,
-            sound_effect,
-            add.entity,
-            false,
-            None,
+            emitter_spatial_settings.unwrap_or(&spatial_settings),
+            &mut voices,
+            &asset_server,
+            &packet_assets,
+            &packet_sets,
+            packets.as_deref(),
+            &earshot,
+            time.elapsed(),
+            entity,
+            request,
+            selection_state.as_deref_mut(),
+        );
+    }
+}
+
+/// Retries the sound effects that are waiting for their packet to load, giving
+/// up once the [`PacketLoadTimeout`] passes.
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn play_awaiting_sound_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    timeout: Res<PacketLoadTimeout>,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    sample_rate: Res<SampleRate>,
+    spatial_settings: Res<SpatialSoundEffectSettings>,
+    mut voices: ResMut<Voices>,
+    asset_server: Res<AssetServer>,
+    packet_assets: Res<Assets<PacketAsset>>,
+    packet_sets: Res<LoadedSoundEffectPackets>,
+    packets: Option<Res<SoundEffectPacketAssetCollection>>,
+    earshot: Earshot,
+    mut query: Query<
+        (
+            Entity,
+            &SoundEffect,
+            &AwaitingPacket,
+            Option<&mut SoundEffectRng>,
+            Option<&mut SoundSelectionState>,
+            Option<&SpatialSoundEffectSettings>,
         ),
-        _ => panic!("Sound effect type not supported yet"),
+        (Without<PendingSoundEffect>, Without<SoundEffectStopped>),
+    >,
+) {
+    let now = time.elapsed();
+
+    for (entity, request, awaiting, request_rng, mut selection_state, emitter_spatial_settings) in
+        &mut query
+    {
+        if now.saturating_sub(awaiting.since) >= timeout.0 {
+            commands.entity(entity).remove::<AwaitingPacket>();
+            report_playback_failure(
+                &mut commands,
+                entity,
+                request.source.packet_path(),
+                request.source.id(),
+                SoundEffectPlaybackError::PacketLoadTimedOut,
+            );
+            continue;
+        }
+
+        start_sound_effect(
+            commands.reborrow(),
+            emitter_rng(request_rng, rng.as_mut()),
+            &sample_rate,
+            emitter_spatial_settings.unwrap_or(&spatial_settings),
+            &mut voices,
+            &asset_server,
+            &packet_assets,
+            &packet_sets,
+            packets.as_deref(),
+            &earshot,
+            now,
+            entity,
+            request,
+            selection_state.as_deref_mut(),
+        );
+    }
+}
+
+/// Starts the sound effect requested on the entity, reporting a
+/// [`SoundEffectPlaybackFailed`] if it cannot be played.
+///
+/// If the packet or its audio samples are still loading, the entity is marked
+/// with [`AwaitingPacket`] and retried by [`play_awaiting_sound_effects`]. If
+/// the emitter is out of earshot, the sound effect starts out as a
+/// [`VirtualSoundEffect`].
+fn start_sound_effect(
+    mut commands: Commands,
+    rng: &mut impl Rng,
+    sample_rate: &Res<SampleRate>,
+    spatial_settings: &SpatialSoundEffectSettings,
+    voices: &mut Voices,
+    asset_server: &AssetServer,
+    packet_assets: &Assets<PacketAsset>,
+    packet_sets: &LoadedSoundEffectPackets,
+    packets: Option<&SoundEffectPacketAssetCollection>,
+    earshot: &Earshot,
+    now: Duration,
+    entity: Entity,
+    request: &SoundEffect,
+    selection_state: Option<&mut SoundSelectionState>,
+) {
+    let (packet_path, sound_effect_id) = (request.source.packet_path(), request.source.id());
+
+    let mut new_selection_state = None;
+    let selection_state =
+        selection_state.unwrap_or_else(|| new_selection_state.insert(Default::default()));
+
+    let _span = info_span!("", packet_path = ?packet_path, sound_effect_id).entered();
+
+    let result =
+        resolve_packet_handle(&request.source, packet_sets, packets).and_then(|packet_handle| {
+            if !asset_server.is_loaded_with_dependencies(packet_handle.id()) {
+                return Err(match asset_server.get_load_state(packet_handle.id()) {
+                    Some(LoadState::Failed(_)) => SoundEffectPlaybackError::PacketLoadFailed,
+                    _ => SoundEffectPlaybackError::PacketNotLoaded,
+                });
+            }
+
+            let packet = packet_assets
+                .get(packet_handle.id())
+                .ok_or(SoundEffectPlaybackError::PacketNotLoaded)?;
+            let sound_effect = packet
+                .sound_effect(sound_effect_id)
+                .ok_or(SoundEffectPlaybackError::SoundEffectNotFound)?;
+
+            if earshot.is_out_of_earshot(entity, request, spatial_settings) {
+                start_virtual_sound_effect(
+                    commands.reborrow(),
+                    packet_handle,
+                    sound_effect,
+                    entity,
+                    now,
+                );
+                return Ok(());
+            }
+
+            debug!("Playing sound effect");
+
+            play_sound_effect(
+                commands.reborrow(),
+                rng,
+                sample_rate,
+                voices,
+                packet_handle,
+                packet,
+                sound_effect,
+                now,
+                entity,
+                request.spatial,
+                request.category,
+                request.is_interruptible(),
+                request.selection,
+                selection_state,
+                Some(spatial_settings),
+            )
+        });
+
+    // Keep what was played so the next pick of the emitter can avoid it.
+    if let Some(selection_state) = new_selection_state
+        && !selection_state.is_empty()
+    {
+        commands.entity(entity).try_insert(selection_state);
+    }
+
+    match result {
+        Err(SoundEffectPlaybackError::PacketNotLoaded) => {
+            debug!("Waiting for packet to load");
+            // Keep the time of the first attempt so the timeout still applies.
+            commands
+                .entity(entity)
+                .try_insert_if_new(AwaitingPacket { since: now });
+        }
+        Err(reason) => {
+            commands.entity(entity).try_remove::<AwaitingPacket>();
+            report_playback_failure(&mut commands, entity, packet_path, sound_effect_id, reason);
+        }
+        Ok(()) => {
+            commands.entity(entity).try_remove::<AwaitingPacket>();
+        }
+    }
+}
+
+/// Resolves the packet handle of a sound effect source.
+///
+/// Packet paths are looked up in the spawned packet sets first, then in the
+/// packet collection.
+fn resolve_packet_handle(
+    source: &SoundEffectSource,
+    packet_sets: &LoadedSoundEffectPackets,
+    packets: Option<&SoundEffectPacketAssetCollection>,
+) -> Result<Handle<PacketAsset>, SoundEffectPlaybackError> {
+    match source {
+        SoundEffectSource::Path { packet_path, .. } => {
+            if let Some(packet) = packet_sets.get(packet_path) {
+                return Ok(packet);
+            }
+
+            match packets {
+                Some(packets) => packets
+                    .get(packet_path)
+                    .ok_or(SoundEffectPlaybackError::PacketNotFound),
+                // Nothing has been loaded yet, so the packets themselves might
+                // still be loading.
+                None if packet_sets.is_empty() => Err(SoundEffectPlaybackError::PacketNotLoaded),
+                None => Err(SoundEffectPlaybackError::PacketNotFound),
+            }
+        }
+        SoundEffectSource::Packet { packet, .. } => Ok(packet.clone()),
     }
 }
 
@@ -192,57 +584,268 @@ fn on_sound_effect_player_added<SoundEffectKeyT>(
 struct SoundPlayerData {
     sample_player: SamplePlayer,
     playback_settings: PlaybackSettings,
+    envelope: SoundEnvelope,
 }
 
-/// Loads a sound and returns the data needed to spawn a player, or `None` if
-/// loading fails.
+/// Loads a sound and returns the data needed to spawn a player.
 fn load_sound(
     rng: &mut impl Rng,
     sample_rate: &Res<SampleRate>,
-    sound_config: &Res<SoundConfig>,
     packet: &PacketAsset,
     sound: &Sound,
-) -> Option<SoundPlayerData> {
-    let source = packet.audio_sample_handle(sound).or_else(|| {
-        error!("Audio sample handle does not exist");
-        None
-    })?;
+) -> Result<SoundPlayerData, SoundEffectPlaybackError> {
+    let source = packet
+        .audio_sample_handle(sound)
+        .ok_or(SoundEffectPlaybackError::AudioSampleNotFound)?;
 
     let (sample_player, playback_settings) =
-        sound.audio_sample_settings(rng, sound_config, sample_rate.get(), &source);
+        sound.audio_sample_settings(rng, sample_rate.get(), &source);
 
-    Some(SoundPlayerData {
+    Ok(SoundPlayerData {
         sample_player,
         playback_settings,
+        envelope: SoundEnvelope {
+            attack: sound.attack_duration(),
+            release: sound.release_duration(),
+        },
     })
 }
 
+/// Plays the sound effect on the given entity according to its
+/// [`SfxType`].
+///
+/// Types that are not known fall back to playing the first sound once, so an
+/// unexpected packet never takes the game down.
 #[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
-fn play_random_looping_sound_effect(
+fn play_sound_effect(
+    commands: Commands,
+    rng: &mut impl Rng,
+    sample_rate: &Res<SampleRate>,
+    voices: &mut Voices,
+    packet_handle: Handle<PacketAsset>,
+    packet: &PacketAsset,
+    sound_effect: &Sfx,
+    now: Duration,
+    entity: Entity,
+    spatial: bool,
+    category: SoundCategory,
+    interruptible: bool,
+    selection: Option<SoundSelection>,
+    selection_state: &mut SoundSelectionState,
+    spatial_settings: Option<&SpatialSoundEffectSettings>,
+) -> Result<(), SoundEffectPlaybackError> {
+    let (sound, looping) = match sound_effect.typ {
+        SfxType::RandomLooping => {
+            return play_random_looping_sound_effect(
+                commands,
+                rng,
+                sample_rate,
+                voices,
+                packet_handle,
+                packet,
+                sound_effect,
+                entity,
+                spatial,
+                category,
+                interruptible,
+                selection,
+                selection_state,
+                spatial_settings,
+            );
+        }
+        SfxType::Sequential | SfxType::SequentialLooping => {
+            return play_sequential_sound_effect(
+                commands,
+                rng,
+                sample_rate,
+                voices,
+                packet_handle,
+                packet,
+                sound_effect,
+                now,
+                entity,
+                matches!(sound_effect.typ, SfxType::SequentialLooping),
+                spatial,
+                category,
+                interruptible,
+                spatial_settings,
+            );
+        }
+        SfxType::Random => (
+            select_sound(rng, packet, sound_effect, selection, selection_state),
+            false,
+        ),
+        SfxType::Looped => (sound_effect.sounds.first().cloned(), true),
+        SfxType::Normal => (sound_effect.sounds.first().cloned(), false),
+        #[allow(
+            unreachable_patterns,
+            reason = "Newer packets may declare types we do not know about yet"
+        )]
+        _ => {
+            warn!("Sound effect type not supported, playing it as a normal sound effect");
+            (sound_effect.sounds.first().cloned(), false)
+        }
+    };
+
+    let sound = sound.ok_or(SoundEffectPlaybackError::NoSounds)?;
+
+    play_single_sound_effect(
+        commands,
+        rng,
+        sample_rate,
+        voices,
+        packet_handle,
+        packet,
+        sound_effect,
+        &sound,
+        entity,
+        looping,
+        spatial,
+        category,
+        interruptible,
+        spatial_settings,
+    )
+}
+
+/// Plays a single sound of the sound effect, either once or looped forever.
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn play_single_sound_effect(
     mut commands: Commands,
     rng: &mut impl Rng,
     sample_rate: &Res<SampleRate>,
-    sound_config: &Res<SoundConfig>,
+    voices: &mut Voices,
     packet_handle: Handle<PacketAsset>,
     packet: &PacketAsset,
     sound_effect: &Sfx,
+    sound: &Sound,
     entity: Entity,
+    looping: bool,
     spatial: bool,
+    category: SoundCategory,
+    interruptible: bool,
     spatial_settings: Option<&SpatialSoundEffectSettings>,
-) {
-    if sound_effect.sounds.is_empty() {
-        debug!("Sound effect has no sounds");
-        return;
-    }
+) -> Result<(), SoundEffectPlaybackError> {
+    let data = load_sound(rng, sample_rate, packet, sound)?;
 
-    let Some(sound) = sound_effect.random_sound(rng) else {
-        error!("Random sound does not exist");
-        return;
-    };
-    let Some(data) = load_sound(rng, sample_rate, sound_config, packet, &sound) else {
-        return;
+    let sample_player = if looping {
+        data.sample_player.looping()
+    } else {
+        data.sample_player
     };
 
+    commands.entity(entity).try_insert((
+        #[cfg(feature = "entity_names")]
+        Name::new(format!(
+            "Sound effect {} - {}",
+            packet.display_name(),
+            sound.file_stem,
+        )),
+        PacketAssetHandle(packet_handle),
+        SoundEffectId(sound_effect.id),
+    ));
+
+    spawn_sound_player_child(
+        &mut commands,
+        voices,
+        sound_effect,
+        entity,
+        None,
+        #[cfg(feature = "entity_names")]
+        sound.file_stem.clone(),
+        sample_player,
+        data.playback_settings,
+        data.envelope,
+        (),
+        spatial,
+        category,
+        interruptible,
+        spatial_settings,
+    )
+}
+
+/// Plays the sounds of the sound effect one after the other, optionally
+/// starting again from the first sound once the last one finishes.
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn play_sequential_sound_effect(
+    mut commands: Commands,
+    rng: &mut impl Rng,
+    sample_rate: &Res<SampleRate>,
+    voices: &mut Voices,
+    packet_handle: Handle<PacketAsset>,
+    packet: &PacketAsset,
+    sound_effect: &Sfx,
+    now: Duration,
+    entity: Entity,
+    looping: bool,
+    spatial: bool,
+    category: SoundCategory,
+    interruptible: bool,
+    spatial_settings: Option<&SpatialSoundEffectSettings>,
+) -> Result<(), SoundEffectPlaybackError> {
+    let sound = sound_effect
+        .sounds
+        .first()
+        .ok_or(SoundEffectPlaybackError::NoSounds)?;
+    let data = load_sound(rng, sample_rate, packet, sound)?;
+
+    let playback_settings = data.playback_settings.with_on_complete(OnComplete::Remove);
+
+    commands.entity(entity).try_insert((
+        #[cfg(feature = "entity_names")]
+        Name::new(format!(
+            "Sequential sound effect {} - {}",
+            packet.display_name(),
+            sound.file_stem,
+        )),
+        PacketAssetHandle(packet_handle),
+        SoundEffectId(sound_effect.id),
+        SequentialSoundEffect {
+            next_index: 1,
+            looping,
+            started: now,
+        },
+    ));
+
+    spawn_sound_player_child(
+        &mut commands,
+        voices,
+        sound_effect,
+        entity,
+        None,
+        #[cfg(feature = "entity_names")]
+        sound.file_stem.clone(),
+        data.sample_player,
+        playback_settings,
+        data.envelope,
+        SequentialSoundPlayerMarker,
+        spatial,
+        category,
+        interruptible,
+        spatial_settings,
+    )
+}
+
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn play_random_looping_sound_effect(
+    mut commands: Commands,
+    rng: &mut impl Rng,
+    sample_rate: &Res<SampleRate>,
+    voices: &mut Voices,
+    packet_handle: Handle<PacketAsset>,
+    packet: &PacketAsset,
+    sound_effect: &Sfx,
+    entity: Entity,
+    spatial: bool,
+    category: SoundCategory,
+    interruptible: bool,
+    selection: Option<SoundSelection>,
+    selection_state: &mut SoundSelectionState,
+    spatial_settings: Option<&SpatialSoundEffectSettings>,
+) -> Result<(), SoundEffectPlaybackError> {
+    let sound = select_sound(rng, packet, sound_effect, selection, selection_state)
+        .ok_or(SoundEffectPlaybackError::NoSounds)?;
+    let data = load_sound(rng, sample_rate, packet, &sound)?;
+
     let playback_settings = data.playback_settings.with_on_complete(OnComplete::Remove);
 
     commands.entity(entity).try_insert((
@@ -257,71 +860,171 @@ fn play_random_looping_sound_effect(
         RandomLoopingSoundEffect,
     ));
 
-    spawn_random_looping_sound_player_child(
+    spawn_sound_player_child(
         &mut commands,
+        voices,
+        sound_effect,
         entity,
+        None,
         #[cfg(feature = "entity_names")]
         sound.file_stem.clone(),
         data.sample_player,
         playback_settings,
+        data.envelope,
+        RandomLoopingSoundPlayerMarker,
         spatial,
+        category,
+        interruptible,
         spatial_settings,
-    );
+    )
+}
+
+/// Sets up a sound effect that is out of earshot as a [`VirtualSoundEffect`]
+/// without playing anything, so that it is played once the listener comes
+/// close.
+///
+/// A sequence is set up as if its first sound started now.
+fn start_virtual_sound_effect(
+    mut commands: Commands,
+    packet_handle: Handle<PacketAsset>,
+    sound_effect: &Sfx,
+    entity: Entity,
+    now: Duration,
+) {
+    debug!("Sound effect out of earshot, starting it virtual");
+
+    let mut entity = commands.entity(entity);
+    entity.try_insert((
+        PacketAssetHandle(packet_handle),
+        SoundEffectId(sound_effect.id),
+        VirtualSoundEffect::new(now),
+    ));
+
+    match sound_effect.typ {
+        SfxType::RandomLooping => {
+            entity.try_insert(RandomLoopingSoundEffect);
+        }
+        SfxType::Sequential | SfxType::SequentialLooping => {
+            entity.try_insert(SequentialSoundEffect {
+                next_index: 1,
+                looping: matches!(sound_effect.typ, SfxType::SequentialLooping),
+                started: now,
+            });
+        }
+        _ => {}
+    }
 }
 
-fn random_looping_sound_player_bundle(
+fn sound_player_bundle(
     #[cfg(feature = "entity_names")] name: String,
     sample_player: SamplePlayer,
     playback_settings: PlaybackSettings,
+    marker: impl Bundle,
 ) -> impl Bundle {
     (
         #[cfg(feature = "entity_names")]
         Name::new(name),
-        RandomLoopingSoundPlayerMarker,
+        marker,
         sample_player,
         playback_settings,
     )
 }
 
-/// Spawns a random looping sound player child, optionally with spatial audio.
-fn spawn_random_looping_sound_player_child(
+/// Spawns a sound player child, optionally with spatial audio.
+///
+/// If an idle player of a previous sound is given, the sound plays on it
+/// instead, reusing its envelope and spatial nodes so that the node count stays
+/// the same however many sounds a loop cycles through.
+///
+/// The `marker` is inserted on the child so the matching removal observer can
+/// tell which kind of sound effect the child belongs to.
+///
+/// Nothing is spawned if the voice budget is exhausted and no playing voice is
+/// less important than this one.
+fn spawn_sound_player_child(
     commands: &mut Commands,
+    voices: &mut Voices,
+    sound_effect: &Sfx,
     parent: Entity,
+    idle_player: Option<Entity>,
     #[cfg(feature = "entity_names")] name: String,
     sample_player: SamplePlayer,
     playback_settings: PlaybackSettings,
+    envelope: SoundEnvelope,
+    marker: impl Bundle,
     spatial: bool,
+    category: SoundCategory,
+    interruptible: bool,
     spatial_settings: Option<&SpatialSoundEffectSettings>,
-) {
-    if spatial {
-        let spatial_node = spatial_settings
-            .map(|s| s.create_node())
-            .unwrap_or_default();
-        commands.entity(parent).with_children(|parent| {
-            parent.spawn((
-                #[cfg(feature = "entity_names")]
-                Name::new(name),
-                RandomLoopingSoundPlayerMarker,
-                sample_player,
-                playback_settings,
-                sample_effects![(
+) -> Result<(), SoundEffectPlaybackError> {
+    let voice = Voice::new(
+        sound_effect.priority,
+        sample_player.volume.linear(),
+        interruptible,
+    );
+    if !voices.make_room(commands, &voice) {
+        return Err(SoundEffectPlaybackError::VoiceLimitReached);
+    }
+
+    let base_speed = DopplerBaseSpeed(playback_settings.speed);
+    let bundle = (
+        SoundCategoryPool::new(category, spatial),
+        sound_player_bundle(
+            #[cfg(feature = "entity_names")]
+            name,
+            sample_player,
+            playback_settings,
+            marker,
+        ),
+        envelope,
+        voice,
+    );
+
+    if let Some(idle_player) = idle_player {
+        let mut child = commands.entity(idle_player);
+        child.try_insert(bundle);
+        if spatial {
+            child.try_insert(base_speed);
+        }
+
+        // Count the voice right away so sounds started in the same frame see
+        // it, and give the slot back if the idle player turns out to be gone,
+        // as then no `Voice` is ever inserted to be removed.
+        voices.insert(idle_player, parent, voice);
+        commands.queue(move |world: &mut World| {
+            if world.get_entity(idle_player).is_err() {
+                world.resource_mut::<Voices>().release(idle_player);
+            }
+        });
+
+        let mut child = commands.entity(idle_player);
+        envelope.restart_node(&mut child);
+
+        return Ok(());
+    }
+
+    let parent_entity = parent;
+    commands.entity(parent).with_children(|parent| {
+        let mut child = parent.spawn(bundle);
+        voices.insert(child.id(), parent_entity, voice);
+
+        envelope.spawn_node(&mut child);
+
+        if spatial {
+            let spatial_node = spatial_settings
+                .map(|s| s.create_node())
+                .unwrap_or_default();
+            child
+                .insert((Transform::default(), base_speed))
+                .with_related::<EffectOf>((
                     #[cfg(feature = "entity_names")]
                     Name::new("Spatial basic node"),
                     spatial_node,
-                )],
-                Transform::default(),
-            ));
-        });
-    } else {
-        commands.entity(parent).with_children(|parent| {
-            parent.spawn(random_looping_sound_player_bundle(
-                #[cfg(feature = "entity_names")]
-                name,
-                sample_player,
-                playback_settings,
-            ));
-        });
-    }
+                ));
+        }
+    });
+
+    Ok(())
 }
 
 /// Marker component for the child entity that plays random looping sounds.
@@ -331,25 +1034,59 @@ fn spawn_random_looping_sound_player_child(
 #[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
 struct RandomLoopingSoundPlayerMarker;
 
-/// When the random looping sound player's [`SamplePlayer`] is removed (sound
-/// finished), spawn a new child with a new random sound.
+/// Marker component for the child entity that plays sequential sounds.
+#[derive(Component)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+struct SequentialSoundPlayerMarker;
+
+/// When a random looping or sequential sound player's [`SamplePlayer`] is
+/// removed (sound finished), play the next sound on the same child, or leave it
+/// for [`play_pending_next_sounds`] in [`Mode::deferred`].
 #[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
-fn on_random_looping_sound_player_removed(
+fn on_sound_player_finished(
     remove: On<Remove, SamplePlayer>,
     mut commands: Commands,
+    mode: Res<Mode>,
     mut rng: Single<&mut WyRand, With<GlobalRng>>,
     sample_rate: Res<SampleRate>,
-    sound_config: Res<SoundConfig>,
     spatial_settings: Res<SpatialSoundEffectSettings>,
+    mut voices: ResMut<Voices>,
     packet_assets: Res<Assets<PacketAsset>>,
-    child_of_query: Query<&ChildOf, With<RandomLoopingSoundPlayerMarker>>,
-    parent_query: Query<
-        (&PacketAssetHandle, &SoundEffectId, Has<SpatialSoundEffect>),
-        With<RandomLoopingSoundEffect>,
+    time: Res<Time>,
+    mut schedule: ResMut<NextSoundSchedule>,
+    mut durations: ResMut<SoundDurations>,
+    child_of_query: Query<
+        &ChildOf,
+        (
+            Without<StolenVoice>,
+            Or<(
+                With<RandomLoopingSoundPlayerMarker>,
+                With<SequentialSoundPlayerMarker>,
+            )>,
+        ),
+    >,
+    mut parent_query: Query<
+        (
+            &PacketAssetHandle,
+            &SoundEffectId,
+            &SoundEffect,
+            Option<&mut SequentialSoundEffect>,
+            Has<SoundEffectPaused>,
+            Option<&mut SoundEffectRng>,
+            Option<&mut SoundSelectionState>,
+            Option<&SpatialSoundEffectSettings>,
+        ),
+        (
+            Without<VirtualSoundEffect>,
+            Without<SoundEffectStopped>,
+            Or<(With<RandomLoopingSoundEffect>, With<SequentialSoundEffect>)>,
+        ),
     >,
 ) {
-    // Check if the removed entity is a random looping sound player child (at
-    // the least, it needs a parent to be so)
+    // Check if the removed entity is a random looping or sequential sound
+    // player child (at the least, it needs a parent to be so)
     let Ok(child_of) = child_of_query.get(remove.entity) else {
         return;
     };
@@ -357,96 +1094,465 @@ fn on_random_looping_sound_player_removed(
     let parent_entity = child_of.parent();
 
     // Get the parent's packet and sound effect info.
-    let Ok((packet_handle, sound_effect_id, is_spatial)) = parent_query.get(parent_entity) else {
+    let Ok((
+        packet_handle,
+        sound_effect_id,
+        request,
+        sequential,
+        paused,
+        request_rng,
+        selection_state,
+        emitter_spatial_settings,
+    )) = parent_query.get_mut(parent_entity)
+    else {
         // Parent might have been despawned, that's fine.
         return;
     };
 
-    let _span = info_span!("", sound_effect_id = sound_effect_id.0).entered();
+    // Keep the player and its effects around for the next sound.
+    commands.entity(remove.entity).try_remove::<Voice>();
+    voices.release(remove.entity);
 
-    let Some(packet) = packet_assets.get(packet_handle.id()) else {
-        error!("Sound effect packet asset not loaded");
-        return;
-    };
-    let Some(sound_effect) = packet.sound_effect(sound_effect_id.0) else {
-        error!("Sound effect does not exist");
+    // Remember how long the sound played for, so that the sequence can skip it
+    // while virtual. A pause would make it seem longer than it is.
+    if let Some(sequential) = &sequential
+        && let Some(index) = sequential.next_index.checked_sub(1)
+        && !paused
+    {
+        durations.record(
+            packet_handle.id(),
+            sound_effect_id.0,
+            index,
+            time.elapsed().saturating_sub(sequential.started),
+        );
+    }
+
+    let rng = emitter_rng(request_rng, rng.as_mut());
+
+    // Random looping sounds may wait out a gap before the next one starts.
+    if sequential.is_none() {
+        let gap = random_looping_gap(&packet_assets, packet_handle, sound_effect_id.0, request)
+            .sample(rng);
+        if !gap.is_zero() {
+            debug!(?gap, "Waiting before next random looping sound");
+            schedule.schedule(time.elapsed() + gap, parent_entity);
+            commands
+                .entity(parent_entity)
+                .try_insert(IdleSoundPlayer(remove.entity));
+            return;
+        }
+    }
+
+    // A paused sound effect plays its next sound once it is resumed.
+    if mode.deferred || paused {
+        commands
+            .entity(parent_entity)
+            .try_insert((PendingNextSound, IdleSoundPlayer(remove.entity)));
         return;
-    };
-    let Some(sound) = sound_effect.random_sound(rng.as_mut()) else {
-        error!("Random sound does not exist");
+    }
+
+    play_next_sound(
+        commands.reborrow(),
+        rng,
+        &sample_rate,
+        emitter_spatial_settings.unwrap_or(&spatial_settings),
+        &mut voices,
+        &packet_assets,
+        &mut schedule,
+        time.elapsed(),
+        parent_entity,
+        packet_handle,
+        sound_effect_id.0,
+        Some(remove.entity),
+        request,
+        sequential.map(Mut::into_inner),
+        selection_state.map(Mut::into_inner),
+    );
+}
+
+/// When the voice of a sound player child is stolen, let its sound effect
+/// compete for a voice again after [`VOICE_RETRY_DELAY`], so that a busy
+/// moment does not silence an ambience for good.
+///
+/// Random looping sounds also wait out their gap. One-shot sounds are over.
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn on_voice_stolen(
+    remove: On<Remove, StolenVoice>,
+    mut rng: Single<&mut WyRand, With<GlobalRng>>,
+    packet_assets: Res<Assets<PacketAsset>>,
+    time: Res<Time>,
+    mut schedule: ResMut<NextSoundSchedule>,
+    child_of_query: Query<&ChildOf>,
+    mut parent_query: Query<
+        (
+            &PacketAssetHandle,
+            &SoundEffectId,
+            &SoundEffect,
+            Has<RandomLoopingSoundEffect>,
+            Has<SequentialSoundEffect>,
+            Option<&mut SoundEffectRng>,
+        ),
+        (Without<VirtualSoundEffect>, Without<SoundEffectStopped>),
+    >,
+) {
+    let Ok(child_of) = child_of_query.get(remove.entity) else {
         return;
     };
-    let Some(data) = load_sound(&mut *rng, &sample_rate, &sound_config, packet, &sound) else {
+    let parent_entity = child_of.parent();
+
+    let Ok((packet_handle, sound_effect_id, request, random_looping, sequential, request_rng)) =
+        parent_query.get_mut(parent_entity)
+    else {
         return;
     };
 
-    let playback_settings = data.playback_settings.with_on_complete(OnComplete::Remove);
+    let retry_at = time.elapsed() + VOICE_RETRY_DELAY;
 
-    commands.entity(remove.entity).try_despawn();
-    spawn_random_looping_sound_player_child(
-        &mut commands,
-        parent_entity,
-        #[cfg(feature = "entity_names")]
-        sound.file_stem.clone(),
-        data.sample_player,
-        playback_settings,
-        is_spatial,
-        Some(&*spatial_settings),
-    );
+    if random_looping {
+        let gap = random_looping_gap(&packet_assets, packet_handle, sound_effect_id.0, request)
+            .sample(emitter_rng(request_rng, rng.as_mut()));
+        let retry_at = time.elapsed() + gap.max(VOICE_RETRY_DELAY);
+        debug!(entity = ?parent_entity, "Voice stolen, retrying next random looping sound later");
+        schedule.schedule(retry_at, parent_entity);
+    } else if sequential {
+        debug!(entity = ?parent_entity, "Voice stolen, retrying next sequential sound later");
+        schedule.schedule(retry_at, parent_entity);
+    } else if is_looped(&packet_assets, Some((packet_handle, sound_effect_id))) {
+        debug!(entity = ?parent_entity, "Voice stolen, restarting looped sound later");
+        schedule.schedule_restart(retry_at, parent_entity);
+    } else {
+        debug!(entity = ?parent_entity, "Voice stolen, one-shot sound effect is over");
+    }
+}
 
-    debug!(sound = sound.file_stem, "Playing next random looping sound");
+/// The gap before the next sound of a random looping sound effect, from the
+/// emitter, then the packet's sidecar file. See [`RandomLoopingGap`].
+fn random_looping_gap(
+    packet_assets: &Assets<PacketAsset>,
+    packet_handle: &Handle<PacketAsset>,
+    sound_effect_id: SfxId,
+    request: &SoundEffect,
+) -> RandomLoopingGap {
+    request
+        .gap
+        .or_else(|| {
+            packet_assets
+                .get(packet_handle.id())?
+                .sound_effect_override(sound_effect_id)?
+                .gap
+        })
+        .unwrap_or_default()
 }
 
+/// Plays the next sound of the random looping and sequential sound effects
+/// whose previous sound finished in [`Mode::deferred`], while paused, or whose
+/// [`RandomLoopingGap`] ended.
 #[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
-fn on_spatial_sound_effect_added(
-    add: On<Add, SpatialSoundEffect>,
+fn play_pending_next_sounds(
     mut commands: Commands,
     mut rng: Single<&mut WyRand, With<GlobalRng>>,
     sample_rate: Res<SampleRate>,
-    sound_config: Res<SoundConfig>,
     spatial_settings: Res<SpatialSoundEffectSettings>,
+    mut voices: ResMut<Voices>,
     packet_assets: Res<Assets<PacketAsset>>,
-    query: Query<&SpatialSoundEffect>,
+    time: Res<Time>,
+    mut schedule: ResMut<NextSoundSchedule>,
+    mut query: Query<
+        (
+            Entity,
+            &PacketAssetHandle,
+            &SoundEffectId,
+            &SoundEffect,
+            Option<&mut SequentialSoundEffect>,
+            Option<&mut SoundEffectRng>,
+            Option<&mut SoundSelectionState>,
+            Option<&IdleSoundPlayer>,
+            Option<&SpatialSoundEffectSettings>,
+        ),
+        (
+            With<PendingNextSound>,
+            Without<SoundEffectPaused>,
+            Without<VirtualSoundEffect>,
+            Without<SoundEffectStopped>,
+            Or<(With<RandomLoopingSoundEffect>, With<SequentialSoundEffect>)>,
+        ),
+    >,
 ) {
-    let Ok(spatial_sound_effect) = query.get(add.entity) else {
-        error!("Spatial sound effect not found");
-        return;
-    };
-
-    let _span = info_span!(
-        "",
-        packet_path = ?spatial_sound_effect.packet.path(),
-        sound_effect_id = spatial_sound_effect.id,
-    )
-    .entered();
+    for (
+        entity,
+        packet_handle,
+        sound_effect_id,
+        request,
+        sequential,
+        request_rng,
+        selection_state,
+        idle_player,
+        emitter_spatial_settings,
+    ) in &mut query
+    {
+        commands
+            .entity(entity)
+            .remove::<(PendingNextSound, IdleSoundPlayer)>();
 
-    debug!("Playing spatial sound effect");
+        let now = time.elapsed();
+        play_next_sound(
+            commands.reborrow(),
+            emitter_rng(request_rng, rng.as_mut()),
+            &sample_rate,
+            emitter_spatial_settings.unwrap_or(&spatial_settings),
+            &mut voices,
+            &packet_assets,
+            &mut schedule,
+            now,
+            entity,
+            packet_handle,
+            sound_effect_id.0,
+            idle_player.map(|idle_player| idle_player.0),
+            request,
+            sequential.map(Mut::into_inner),
+            selection_state.map(Mut::into_inner),
+        );
+    }
+}
 
-    let packet_handle = spatial_sound_effect.packet.clone();
-    let Some(packet) = packet_assets.get(packet_handle.id()) else {
-        error!("Sound effect packet asset not loaded");
-        return;
-    };
-    let Some(sound_effect) = packet.sound_effect(spatial_sound_effect.id) else {
-        error!("Sound effect does not exist");
-        return;
-    };
+/// Plays the next sound of a random looping or sequential sound effect,
+/// reporting a [`SoundEffectPlaybackFailed`] if it cannot be played.
+///
+/// If no voice is free for it, the sound effect keeps its idle child and tries
+/// again after [`VOICE_RETRY_DELAY`] instead.
+fn play_next_sound(
+    mut commands: Commands,
+    rng: &mut impl Rng,
+    sample_rate: &Res<SampleRate>,
+    spatial_settings: &SpatialSoundEffectSettings,
+    voices: &mut Voices,
+    packet_assets: &Assets<PacketAsset>,
+    schedule: &mut NextSoundSchedule,
+    now: Duration,
+    parent_entity: Entity,
+    packet_handle: &Handle<PacketAsset>,
+    sound_effect_id: SfxId,
+    idle_player: Option<Entity>,
+    request: &SoundEffect,
+    sequential: Option<&mut SequentialSoundEffect>,
+    selection_state: Option<&mut SoundSelectionState>,
+) {
+    let _span = info_span!("", sound_effect_id).entered();
 
-    match sound_effect.typ {
-        SfxType::RandomLooping => play_random_looping_sound_effect(
+    let result = match sequential {
+        Some(sequential) => play_next_sequential_sound(
             commands.reborrow(),
-            &mut rng,
-            &sample_rate,
-            &sound_config,
+            rng,
+            sample_rate,
+            voices,
+            packet_assets,
             packet_handle,
-            packet,
-            sound_effect,
-            add.entity,
-            true,
-            Some(&*spatial_settings),
+            sound_effect_id,
+            now,
+            sequential,
+            parent_entity,
+            idle_player,
+            request.spatial,
+            request.category,
+            request.is_interruptible(),
+            Some(spatial_settings),
         ),
-        _ => panic!("Sound effect type not supported yet"),
+        None => {
+            let mut new_selection_state = None;
+            let selection_state =
+                selection_state.unwrap_or_else(|| new_selection_state.insert(Default::default()));
+
+            let result = play_next_random_looping_sound(
+                commands.reborrow(),
+                rng,
+                sample_rate,
+                voices,
+                packet_assets,
+                packet_handle,
+                sound_effect_id,
+                parent_entity,
+                idle_player,
+                request.spatial,
+                request.category,
+                request.is_interruptible(),
+                request.selection,
+                selection_state,
+                Some(spatial_settings),
+            );
+
+            if let Some(selection_state) = new_selection_state {
+                commands.entity(parent_entity).try_insert(selection_state);
+            }
+
+            result
+        }
+    };
+
+    match result {
+        Ok(()) => {}
+        Err(SoundEffectPlaybackError::VoiceLimitReached) => {
+            debug!("No voice for the next sound, retrying later");
+            if let Some(idle_player) = idle_player {
+                commands
+                    .entity(parent_entity)
+                    .try_insert(IdleSoundPlayer(idle_player));
+            }
+            schedule.schedule(now + VOICE_RETRY_DELAY, parent_entity);
+        }
+        Err(reason) => {
+            if let Some(idle_player) = idle_player {
+                commands.entity(idle_player).try_despawn();
+            }
+            report_playback_failure(
+                &mut commands,
+                parent_entity,
+                packet_handle.path().map(ToString::to_string),
+                sound_effect_id,
+                reason,
+            );
+        }
+    }
+}
+
+/// Plays a new random sound of the random looping sound effect on its idle
+/// child, or on a new child if there is none.
+fn play_next_random_looping_sound(
+    mut commands: Commands,
+    rng: &mut impl Rng,
+    sample_rate: &Res<SampleRate>,
+    voices: &mut Voices,
+    packet_assets: &Assets<PacketAsset>,
+    packet_handle: &Handle<PacketAsset>,
+    sound_effect_id: SfxId,
+    parent_entity: Entity,
+    idle_player: Option<Entity>,
+    spatial: bool,
+    category: SoundCategory,
+    interruptible: bool,
+    selection: Option<SoundSelection>,
+    selection_state: &mut SoundSelectionState,
+    spatial_settings: Option<&SpatialSoundEffectSettings>,
+) -> Result<(), SoundEffectPlaybackError> {
+    let packet = packet_assets
+        .get(packet_handle.id())
+        .ok_or(SoundEffectPlaybackError::PacketNotLoaded)?;
+    let sound_effect = packet
+        .sound_effect(sound_effect_id)
+        .ok_or(SoundEffectPlaybackError::SoundEffectNotFound)?;
+    let sound = select_sound(rng, packet, sound_effect, selection, selection_state)
+        .ok_or(SoundEffectPlaybackError::NoSounds)?;
+    let data = load_sound(rng, sample_rate, packet, &sound)?;
+
+    let playback_settings = data.playback_settings.with_on_complete(OnComplete::Remove);
+
+    spawn_sound_player_child(
+        &mut commands,
+        voices,
+        sound_effect,
+        parent_entity,
+        idle_player,
+        #[cfg(feature = "entity_names")]
+        sound.file_stem.clone(),
+        data.sample_player,
+        playback_settings,
+        data.envelope,
+        RandomLoopingSoundPlayerMarker,
+        spatial,
+        category,
+        interruptible,
+        spatial_settings,
+    )?;
+
+    debug!(sound = sound.file_stem, "Playing next random looping sound");
+
+    Ok(())
+}
+
+/// Plays the next sound in the sequence of the sequential sound effect on its
+/// idle child, or ends the sound effect once the sequence is done.
+fn play_next_sequential_sound(
+    mut commands: Commands,
+    rng: &mut impl Rng,
+    sample_rate: &Res<SampleRate>,
+    voices: &mut Voices,
+    packet_assets: &Assets<PacketAsset>,
+    packet_handle: &Handle<PacketAsset>,
+    sound_effect_id: SfxId,
+    now: Duration,
+    sequential: &mut SequentialSoundEffect,
+    parent_entity: Entity,
+    idle_player: Option<Entity>,
+    spatial: bool,
+    category: SoundCategory,
+    interruptible: bool,
+    spatial_settings: Option<&SpatialSoundEffectSettings>,
+) -> Result<(), SoundEffectPlaybackError> {
+    let packet = packet_assets
+        .get(packet_handle.id())
+        .ok_or(SoundEffectPlaybackError::PacketNotLoaded)?;
+    let sound_effect = packet
+        .sound_effect(sound_effect_id)
+        .ok_or(SoundEffectPlaybackError::SoundEffectNotFound)?;
+
+    if sequential.next_index >= sound_effect.sounds.len() {
+        if !sequential.looping || sound_effect.sounds.is_empty() {
+            debug!("Finished sequential sound effect");
+            commands
+                .entity(parent_entity)
+                .try_remove::<SequentialSoundEffect>();
+            if let Some(idle_player) = idle_player {
+                commands.entity(idle_player).try_despawn();
+            }
+            return Ok(());
+        }
+        sequential.next_index = 0;
     }
+
+    let sound = &sound_effect.sounds[sequential.next_index];
+
+    let data = load_sound(rng, sample_rate, packet, sound)?;
+
+    let playback_settings = data.playback_settings.with_on_complete(OnComplete::Remove);
+
+    spawn_sound_player_child(
+        &mut commands,
+        voices,
+        sound_effect,
+        parent_entity,
+        idle_player,
+        #[cfg(feature = "entity_names")]
+        sound.file_stem.clone(),
+        data.sample_player,
+        playback_settings,
+        data.envelope,
+        SequentialSoundPlayerMarker,
+        spatial,
+        category,
+        interruptible,
+        spatial_settings,
+    )?;
+
+    // Only advance once the sound plays, so a retry plays the same sound.
+    sequential.next_index += 1;
+    sequential.started = now;
+
+    debug!(sound = sound.file_stem, "Playing next sequential sound");
+
+    Ok(())
+}
+
+/// Returns `true` if the sound effect loops a single sound forever.
+fn is_looped(
+    packet_assets: &Assets<PacketAsset>,
+    sound_effect: Option<(&PacketAssetHandle, &SoundEffectId)>,
+) -> bool {
+    sound_effect
+        .and_then(|(packet_handle, sound_effect_id)| {
+            packet_assets
+                .get(packet_handle.id())?
+                .sound_effect(sound_effect_id.0)
+        })
+        .is_some_and(|sound_effect| matches!(sound_effect.typ, SfxType::Looped))
 }
 
 fn count_nodes(
@@ -470,3 +1576,111 @@ fn count_nodes(
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::TaskPoolPlugin;
+    use bevy_time::TimePlugin;
+
+    use super::*;
+    use crate::sound_effects::SoundEffectKey;
+
+    const BIRDS: &str = "DARKOMEN/SOUND/H/BIRDS.H";
+
+    /// The random looping sound effect of `BIRDS.H`.
+    const BIRDS_ID: SfxId = 0;
+
+    /// An app with the sound effect plugin, once the `BIRDS.H` packet and its
+    /// audio samples have loaded from the example assets.
+    fn app() -> (App, Handle<PacketAsset>) {
+        let mut app = App::new();
+        app.add_plugins((
+            TaskPoolPlugin::default(),
+            TimePlugin,
+            AssetPlugin::default(),
+            crate::SoundEffectPlugin::<SoundEffectKey>::new(),
+        ));
+
+        let packet = app.world().resource::<AssetServer>().load(BIRDS);
+        for _ in 0..1000 {
+            app.update();
+            let asset_server = app.world().resource::<AssetServer>();
+            if asset_server.is_loaded_with_dependencies(packet.id()) {
+                return (app, packet);
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("{BIRDS} did not load");
+    }
+
+    /// The children of the emitter that are playing a sound.
+    fn sound_players(app: &App, emitter: Entity) -> Vec<Entity> {
+        let world = app.world();
+        world
+            .get::<Children>(emitter)
+            .into_iter()
+            .flat_map(|children| children.iter())
+            .filter(|&child| world.entity(child).contains::<SamplePlayer>())
+            .collect()
+    }
+
+    /// Ends the sound of the sound player, the way seedling does once its
+    /// sample has played to the end.
+    fn finish_sound(app: &mut App, sound_player: Entity) {
+        app.world_mut()
+            .entity_mut(sound_player)
+            .remove::<SamplePlayer>();
+        app.update();
+    }
+
+    #[test]
+    fn paused_random_loop_waits_for_resume() {
+        let (mut app, packet) = app();
+        let emitter = app
+            .world_mut()
+            .spawn(SoundEffect::new(packet, BIRDS_ID))
+            .id();
+        app.update();
+        let [sound_player] = sound_players(&app, emitter)[..] else {
+            panic!("the random loop should play one sound");
+        };
+
+        app.world_mut().trigger(PauseSoundEffect::new(emitter));
+        finish_sound(&mut app, sound_player);
+        app.update();
+
+        assert!(sound_players(&app, emitter).is_empty());
+        assert!(app.world().entity(emitter).contains::<PendingNextSound>());
+
+        app.world_mut().trigger(ResumeSoundEffect::new(emitter));
+        app.update();
+
+        // The next sound plays on the same child once resumed.
+        assert_eq!(sound_players(&app, emitter), [sound_player]);
+        assert!(!app.world().entity(emitter).contains::<PendingNextSound>());
+    }
+
+    #[test]
+    fn random_loop_reuses_its_effect_nodes() {
+        let (mut app, packet) = app();
+        let emitter = app
+            .world_mut()
+            .spawn(SoundEffect::new(packet, BIRDS_ID))
+            .id();
+        app.update();
+        let [sound_player] = sound_players(&app, emitter)[..] else {
+            panic!("the random loop should play one sound");
+        };
+        let effect_count = |app: &mut App| {
+            let world = app.world_mut();
+            world.query::<&EffectOf>().iter(world).count()
+        };
+        let effects = effect_count(&mut app);
+
+        for _ in 0..5 {
+            finish_sound(&mut app, sound_player);
+            assert_eq!(sound_players(&app, emitter), [sound_player]);
+            assert_eq!(effect_count(&mut app), effects);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_seedling::{
+    SeedlingSystems, firewheel::nodes::spatial_basic::SpatialBasicNode, prelude::*,
+};
+use sound_config::{Decibels, prelude::*};
+#[allow(unused_imports)]
+use tracing::*;
+
+pub(super) struct BusPlugin;
+
+impl Plugin for BusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_sound_category_buses);
+
+        app.add_systems(
+            Last,
+            sync_sound_category_buses
+                .run_if(resource_changed::<SoundConfig>)
+                .before(SeedlingSystems::Acquire),
+        );
+    }
+}
+
+/// The bus that mixes every sound of a [`SoundCategory`] before it feeds the
+/// [`MainBus`].
+///
+/// The bus volume follows the [`SoundConfig`] volume of its category, and
+/// volume changes are smoothed by the node so that moving a volume slider does
+/// not cause zipper noise.
+#[derive(Clone, Copy, Debug, Eq, Hash, NodeLabel, PartialEq)]
+pub struct SoundCategoryBus(pub SoundCategory);
+
+/// The sampler pool that plays the sounds of a [`SoundCategory`] into its
+/// [`SoundCategoryBus`].
+///
+/// Each category has a pool for spatial and one for non-spatial sounds, as
+/// their sample effects differ.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PoolLabel)]
+pub struct SoundCategoryPool {
+    pub category: SoundCategory,
+    pub spatial: bool,
+}
+
+impl SoundCategoryPool {
+    pub fn new(category: SoundCategory, spatial: bool) -> Self {
+        SoundCategoryPool { category, spatial }
+    }
+}
+
+#[inline(always)]
+fn category_volume(sound_config: &SoundConfig, category: SoundCategory) -> Volume {
+    sound_config
+        .category_volume(category, Decibels::IDENTITY)
+        .into()
+}
+
+/// Spawns a bus per category that feeds the [`MainBus`], and the sampler pools
+/// that feed each bus.
+fn spawn_sound_category_buses(mut commands: Commands, sound_config: Res<SoundConfig>) {
+    for category in SoundCategory::ALL {
+        debug!(?category, "Spawning sound category bus");
+
+        commands
+            .spawn((
+                #[cfg(feature = "entity_names")]
+                Name::new(format!("{category:?} bus")),
+                SoundCategoryBus(category),
+                VolumeNode {
+                    volume: category_volume(&sound_config, category),
+                    ..Default::default()
+                },
+            ))
+            .connect(MainBus);
+
+        // The sample effects must match the ones spawned for each sound
+        // player: the envelope volume node, then the spatial node if any.
+        commands
+            .spawn((
+                #[cfg(feature = "entity_names")]
+                Name::new(format!("{category:?} pool")),
+                SamplerPool(SoundCategoryPool::new(category, false)),
+                sample_effects![VolumeNode::default()],
+            ))
+            .connect(SoundCategoryBus(category));
+
+        commands
+            .spawn((
+                #[cfg(feature = "entity_names")]
+                Name::new(format!("{category:?} spatial pool")),
+                SamplerPool(SoundCategoryPool::new(category, true)),
+                sample_effects![VolumeNode::default(), SpatialBasicNode::default()],
+            ))
+            .connect(SoundCategoryBus(category));
+    }
+}
+
+/// Applies [`SoundConfig`] changes to the category buses, so they reach every
+/// sound that is already playing, including endless random loops and muting.
+///
+/// This is the only place the [`SoundConfig`] volume is applied, sample players
+/// only carry the volume of their sound.
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn sync_sound_category_buses(
+    sound_config: Res<SoundConfig>,
+    mut query: Query<(&SoundCategoryBus, &mut VolumeNode)>,
+) {
+    for (bus, mut node) in &mut query {
+        let volume = category_volume(&sound_config, bus.0);
+
+        debug!(category = ?bus.0, ?volume, "Updating sound category bus volume");
+
+        node.volume = volume;
+    }
+}
@@ -0,0 +1,165 @@
+use core::time::Duration;
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use bevy_seedling::prelude::*;
+#[allow(unused_imports)]
+use tracing::*;
+
+use super::{envelope::StopSoundEffect, voice::Voice};
+
+pub(super) struct ControlPlugin;
+
+impl Plugin for ControlPlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(feature = "reflect")]
+        app.register_type::<SoundEffectPaused>();
+
+        app.add_observer(on_pause_sound_effect);
+        app.add_observer(on_resume_sound_effect);
+        app.add_observer(on_voice_added);
+    }
+}
+
+/// Trigger this event to pause the sound effect playing on the given entity.
+///
+/// Random looping and sequential sound effects do not move on to their next
+/// sound while paused.
+#[derive(Clone, Copy, EntityEvent)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct PauseSoundEffect {
+    pub entity: Entity,
+}
+
+impl PauseSoundEffect {
+    /// Creates a new event that pauses the sound effect on the given entity.
+    pub fn new(entity: Entity) -> Self {
+        PauseSoundEffect { entity }
+    }
+}
+
+/// Trigger this event to resume the paused sound effect on the given entity.
+#[derive(Clone, Copy, EntityEvent)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct ResumeSoundEffect {
+    pub entity: Entity,
+}
+
+impl ResumeSoundEffect {
+    /// Creates a new event that resumes the sound effect on the given entity.
+    pub fn new(entity: Entity) -> Self {
+        ResumeSoundEffect { entity }
+    }
+}
+
+/// Marker component for a sound effect that is paused.
+#[derive(Clone, Component, Copy, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct SoundEffectPaused;
+
+/// Stop, fade out, pause and resume the sound effect on an entity.
+pub trait SoundEffectCommandsExt {
+    /// Stops the sound effect, fading each sound out over its packet `RELEASE`
+    /// time.
+    fn stop_sound_effect(&mut self) -> &mut Self;
+
+    /// Stops the sound effect, fading each sound out over the given duration.
+    fn fade_out_sound_effect(&mut self, fade: Duration) -> &mut Self;
+
+    /// Pauses the sound effect.
+    fn pause_sound_effect(&mut self) -> &mut Self;
+
+    /// Resumes the paused sound effect.
+    fn resume_sound_effect(&mut self) -> &mut Self;
+}
+
+impl SoundEffectCommandsExt for EntityCommands<'_> {
+    fn stop_sound_effect(&mut self) -> &mut Self {
+        let entity = self.id();
+        self.commands_mut().trigger(StopSoundEffect::new(entity));
+        self
+    }
+
+    fn fade_out_sound_effect(&mut self, fade: Duration) -> &mut Self {
+        let entity = self.id();
+        self.commands_mut()
+            .trigger(StopSoundEffect::new(entity).with_fade(fade));
+        self
+    }
+
+    fn pause_sound_effect(&mut self) -> &mut Self {
+        let entity = self.id();
+        self.commands_mut().trigger(PauseSoundEffect::new(entity));
+        self
+    }
+
+    fn resume_sound_effect(&mut self) -> &mut Self {
+        let entity = self.id();
+        self.commands_mut().trigger(ResumeSoundEffect::new(entity));
+        self
+    }
+}
+
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn on_pause_sound_effect(
+    pause: On<PauseSoundEffect>,
+    mut commands: Commands,
+    children_query: Query<&Children>,
+    mut settings_query: Query<&mut PlaybackSettings, With<Voice>>,
+) {
+    debug!("Pausing sound effect");
+
+    commands.entity(pause.entity).try_insert(SoundEffectPaused);
+
+    let Ok(children) = children_query.get(pause.entity) else {
+        return;
+    };
+
+    let mut iter = settings_query.iter_many_mut(children);
+    while let Some(mut settings) = iter.fetch_next() {
+        settings.pause();
+    }
+}
+
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn on_resume_sound_effect(
+    resume: On<ResumeSoundEffect>,
+    mut commands: Commands,
+    children_query: Query<&Children>,
+    mut settings_query: Query<&mut PlaybackSettings, With<Voice>>,
+) {
+    debug!("Resuming sound effect");
+
+    commands
+        .entity(resume.entity)
+        .try_remove::<SoundEffectPaused>();
+
+    let Ok(children) = children_query.get(resume.entity) else {
+        return;
+    };
+
+    let mut iter = settings_query.iter_many_mut(children);
+    while let Some(mut settings) = iter.fetch_next() {
+        settings.play();
+    }
+}
+
+/// Pauses sound players that are spawned for a sound effect that is already
+/// paused, e.g., when it was paused in the same frame it was started.
+fn on_voice_added(
+    add: On<Add, Voice>,
+    mut query: Query<(&ChildOf, &mut PlaybackSettings)>,
+    paused_query: Query<(), With<SoundEffectPaused>>,
+) {
+    let Ok((child_of, mut settings)) = query.get_mut(add.entity) else {
+        return;
+    };
+
+    if paused_query.contains(child_of.parent()) {
+        settings.pause();
+    }
+}
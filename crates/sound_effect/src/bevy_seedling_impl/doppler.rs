@@ -0,0 +1,340 @@
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_math::prelude::*;
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use bevy_seedling::{SeedlingSystems, prelude::*, spatial::SpatialListener3D};
+use bevy_time::prelude::*;
+use bevy_transform::prelude::*;
+#[allow(unused_imports)]
+use tracing::*;
+
+use crate::SoundEffect;
+
+pub(super) struct DopplerPlugin {
+    pub(super) settings: Option<DopplerSettings>,
+}
+
+impl Plugin for DopplerPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(settings) = &self.settings {
+            app.insert_resource(settings.clone());
+        }
+
+        #[cfg(feature = "reflect")]
+        {
+            app.register_type::<DopplerSettings>();
+            app.register_type::<DopplerVelocity>();
+            app.register_type::<DopplerBaseSpeed>();
+            app.register_type::<PreviousTranslation>();
+        }
+
+        app.add_systems(
+            Last,
+            apply_doppler_shift
+                .run_if(resource_exists::<DopplerSettings>)
+                .before(SeedlingSystems::Acquire),
+        );
+    }
+}
+
+/// Shifts the pitch of spatial sound effects that move relative to the
+/// [`SpatialListener3D`].
+///
+/// Doppler is off unless this resource exists, e.g., when set with
+/// [`SoundEffectPlugin::with_doppler`].
+///
+/// [`SoundEffectPlugin::with_doppler`]: crate::SoundEffectPlugin::with_doppler
+#[derive(Clone, Resource)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Resource))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct DopplerSettings {
+    /// Scales the relative velocities, from 0 for no shift to 1 for a
+    /// physically accurate shift.
+    pub strength: f32,
+    /// The speed of sound in world units per second.
+    pub speed_of_sound: f32,
+    /// The lowest playback speed factor, reached when moving away fast.
+    pub min_factor: f32,
+    /// The highest playback speed factor, reached when approaching fast.
+    pub max_factor: f32,
+}
+
+impl Default for DopplerSettings {
+    fn default() -> Self {
+        DopplerSettings {
+            strength: 1.0,
+            speed_of_sound: 343.0,
+            min_factor: 0.5,
+            max_factor: 2.0,
+        }
+    }
+}
+
+impl DopplerSettings {
+    /// Scales the relative velocities, from 0 for no shift to 1 for a
+    /// physically accurate shift. Values above 1 exaggerate the shift.
+    pub fn with_strength(mut self, strength: f32) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    /// Sets the speed of sound in world units per second, e.g., lower it for
+    /// a game whose units are larger than meters.
+    pub fn with_speed_of_sound(mut self, speed_of_sound: f32) -> Self {
+        self.speed_of_sound = speed_of_sound;
+        self
+    }
+
+    /// Clamps the playback speed factor to the given range.
+    pub fn with_factor_range(mut self, min_factor: f32, max_factor: f32) -> Self {
+        self.min_factor = min_factor.min(max_factor);
+        self.max_factor = min_factor.max(max_factor);
+        self
+    }
+
+    /// The playback speed factor of a source heard by a listener.
+    ///
+    /// The velocities are in world units per second, and the direction points
+    /// from the listener to the source.
+    fn factor(&self, direction: Vec3, source_velocity: Vec3, listener_velocity: Vec3) -> f32 {
+        // Positive when the source moves away from, or the listener towards,
+        // the other.
+        let source_speed = source_velocity.dot(direction) * self.strength;
+        let listener_speed = listener_velocity.dot(direction) * self.strength;
+
+        // Never let the source outrun its own sound.
+        let source_speed = source_speed.max(-self.speed_of_sound * 0.99);
+
+        let factor = (self.speed_of_sound + listener_speed) / (self.speed_of_sound + source_speed);
+        if factor.is_finite() {
+            factor.clamp(self.min_factor, self.max_factor)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// The velocity of a sound effect emitter or of the [`SpatialListener3D`], in
+/// world units per second.
+///
+/// Entities without this component have their velocity derived from how far
+/// their [`GlobalTransform`] moved since the last frame.
+#[derive(Clone, Component, Copy, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct DopplerVelocity(pub Vec3);
+
+/// The playback speed of a spatial sound before the Doppler shift, i.e., its
+/// random packet pitch.
+#[derive(Clone, Component, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub(super) struct DopplerBaseSpeed(pub(super) f64);
+
+/// Where an entity was last frame, to derive its velocity from.
+#[derive(Clone, Component, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+struct PreviousTranslation(Vec3);
+
+/// Returns the velocity of an entity, from its [`DopplerVelocity`] if it has
+/// one, and tracks its translation for the next frame otherwise.
+fn velocity(
+    commands: &mut Commands,
+    entity: Entity,
+    delta_secs: f32,
+    transform: &GlobalTransform,
+    velocity: Option<&DopplerVelocity>,
+    previous: Option<Mut<PreviousTranslation>>,
+) -> Vec3 {
+    if let Some(velocity) = velocity {
+        return velocity.0;
+    }
+
+    let translation = transform.translation();
+    match previous {
+        Some(mut previous) => {
+            let velocity = if delta_secs > 0.0 {
+                (translation - previous.0) / delta_secs
+            } else {
+                Vec3::ZERO
+            };
+            previous.0 = translation;
+            velocity
+        }
+        None => {
+            commands
+                .entity(entity)
+                .try_insert(PreviousTranslation(translation));
+            Vec3::ZERO
+        }
+    }
+}
+
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn apply_doppler_shift(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<DopplerSettings>,
+    mut listener_query: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            Option<&DopplerVelocity>,
+            Option<&mut PreviousTranslation>,
+        ),
+        (With<SpatialListener3D>, Without<SoundEffect>),
+    >,
+    mut emitter_query: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            Option<&DopplerVelocity>,
+            Option<&mut PreviousTranslation>,
+            &Children,
+        ),
+        With<SoundEffect>,
+    >,
+    mut player_query: Query<(&DopplerBaseSpeed, &mut PlaybackSettings)>,
+) {
+    let delta_secs = time.delta_secs();
+
+    let Some((listener, listener_transform, listener_velocity, listener_previous)) =
+        listener_query.iter_mut().next()
+    else {
+        return;
+    };
+    let listener_translation = listener_transform.translation();
+    let listener_velocity = velocity(
+        &mut commands,
+        listener,
+        delta_secs,
+        listener_transform,
+        listener_velocity,
+        listener_previous,
+    );
+
+    for (entity, transform, emitter_velocity, previous, children) in &mut emitter_query {
+        let emitter_velocity = velocity(
+            &mut commands,
+            entity,
+            delta_secs,
+            transform,
+            emitter_velocity,
+            previous,
+        );
+
+        let direction = (transform.translation() - listener_translation).normalize_or_zero();
+        let factor = settings.factor(direction, emitter_velocity, listener_velocity) as f64;
+
+        let mut iter = player_query.iter_many_mut(children);
+        while let Some((base_speed, mut playback_settings)) = iter.fetch_next() {
+            let speed = base_speed.0 * factor;
+            // Avoid flagging the settings as changed for inaudible differences.
+            if (playback_settings.speed - speed).abs() > 1e-4 {
+                playback_settings.speed = speed;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn settings() -> DopplerSettings {
+        DopplerSettings::default().with_factor_range(0.1, 10.0)
+    }
+
+    #[test]
+    fn stationary_source_is_not_shifted() {
+        let factor = settings().factor(Vec3::X, Vec3::ZERO, Vec3::ZERO);
+
+        assert!((factor - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn approaching_source_is_higher() {
+        // The source is on +X and moves towards the listener.
+        let factor = settings().factor(Vec3::X, Vec3::NEG_X * 34.3, Vec3::ZERO);
+
+        assert!((factor - 343.0 / 308.7).abs() < EPSILON);
+    }
+
+    #[test]
+    fn receding_source_is_lower() {
+        let factor = settings().factor(Vec3::X, Vec3::X * 34.3, Vec3::ZERO);
+
+        assert!((factor - 343.0 / 377.3).abs() < EPSILON);
+    }
+
+    #[test]
+    fn approaching_listener_is_higher() {
+        let factor = settings().factor(Vec3::X, Vec3::ZERO, Vec3::X * 34.3);
+
+        assert!((factor - 377.3 / 343.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn sideways_motion_is_not_shifted() {
+        let factor = settings().factor(Vec3::X, Vec3::Y * 100.0, Vec3::Z * 100.0);
+
+        assert!((factor - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn zero_strength_is_not_shifted() {
+        let settings = settings().with_strength(0.0);
+
+        let factor = settings.factor(Vec3::X, Vec3::NEG_X * 100.0, Vec3::X * 100.0);
+
+        assert!((factor - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn factor_is_clamped() {
+        let settings = DopplerSettings::default();
+
+        let approaching = settings.factor(Vec3::X, Vec3::NEG_X * 300.0, Vec3::ZERO);
+        let receding = settings.factor(Vec3::X, Vec3::X * 1000.0, Vec3::ZERO);
+
+        assert_eq!(approaching, settings.max_factor);
+        assert_eq!(receding, settings.min_factor);
+    }
+
+    #[test]
+    fn supersonic_source_stays_finite() {
+        let settings = settings();
+
+        for speed in [343.0, 1000.0, f32::MAX] {
+            let factor = settings.factor(Vec3::X, Vec3::NEG_X * speed, Vec3::ZERO);
+
+            assert!(factor.is_finite());
+            assert!((settings.min_factor..=settings.max_factor).contains(&factor));
+        }
+    }
+
+    #[test]
+    fn zero_speed_of_sound_is_not_shifted() {
+        let factor = settings()
+            .with_speed_of_sound(0.0)
+            .factor(Vec3::X, Vec3::ZERO, Vec3::ZERO);
+
+        assert!((factor - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn with_factor_range_orders_bounds() {
+        let settings = DopplerSettings::default().with_factor_range(3.0, 0.25);
+
+        assert_eq!(settings.min_factor, 0.25);
+        assert_eq!(settings.max_factor, 3.0);
+    }
+}
@@ -0,0 +1,447 @@
+use core::time::Duration;
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use bevy_seedling::prelude::*;
+use bevy_time::prelude::*;
+#[allow(unused_imports)]
+use tracing::*;
+
+use super::{
+    AwaitingPacket, IdleSoundPlayer, PendingNextSound, PendingSoundEffect, VirtualSoundEffect,
+    voice::Voice,
+};
+use crate::{RandomLoopingSoundEffect, SequentialSoundEffect};
+
+pub(super) struct EnvelopePlugin;
+
+impl Plugin for EnvelopePlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(feature = "reflect")]
+        {
+            app.register_type::<SoundEnvelope>();
+            app.register_type::<EnvelopeNode>();
+            app.register_type::<VolumeFade>();
+            app.register_type::<SoundEffectStopped>();
+        }
+
+        app.add_observer(on_stop_sound_effect);
+        app.add_observer(on_voice_added);
+
+        app.add_systems(Update, tick_volume_fades);
+    }
+}
+
+/// Trigger this event to stop the sound effect playing on the given entity.
+///
+/// Each sound that is still playing fades out over its packet `RELEASE` time,
+/// or the given fade duration, before its player is despawned.
+/// The entity is marked with [`SoundEffectStopped`] so that nothing starts
+/// again on it.
+#[derive(Clone, Copy, EntityEvent)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct StopSoundEffect {
+    pub entity: Entity,
+    /// Overrides the packet `RELEASE` time of every sound. A zero duration
+    /// stops the sounds immediately.
+    pub fade: Option<Duration>,
+}
+
+impl StopSoundEffect {
+    /// Creates a new event that stops the sound effect on the given entity.
+    pub fn new(entity: Entity) -> Self {
+        StopSoundEffect { entity, fade: None }
+    }
+
+    /// Fades the sounds out over the given duration instead of their packet
+    /// `RELEASE` time.
+    pub fn with_fade(mut self, fade: Duration) -> Self {
+        self.fade = Some(fade);
+        self
+    }
+}
+
+/// Marker component for a sound effect that was stopped with
+/// [`StopSoundEffect`].
+///
+/// A stopped sound effect never starts a sound again, even if it was still
+/// waiting for its packet, for its next sound or to become real again.
+#[derive(Clone, Component, Copy, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct SoundEffectStopped;
+
+/// The fade-in and fade-out times of a sound, taken from the packet `ATTACK`
+/// and `RELEASE` fields.
+#[derive(Clone, Component, Copy, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub(super) struct SoundEnvelope {
+    pub(super) attack: Duration,
+    pub(super) release: Duration,
+}
+
+impl SoundEnvelope {
+    /// Spawns the volume node that applies this envelope as an effect of the
+    /// given sample player.
+    ///
+    /// The node is spawned even if the sound neither fades in nor fades out, so
+    /// that [`StopSoundEffect::with_fade`] can fade it out.
+    pub(super) fn spawn_node(&self, sample_player: &mut EntityCommands) {
+        let attack = self.attack;
+        sample_player.with_related_entities::<EffectOf>(|effects| {
+            let mut node = effects.spawn((
+                #[cfg(feature = "entity_names")]
+                Name::new("Envelope volume node"),
+                EnvelopeNode,
+                VolumeNode {
+                    volume: if attack.is_zero() {
+                        Volume::UNITY_GAIN
+                    } else {
+                        Volume::SILENT
+                    },
+                    ..Default::default()
+                },
+            ));
+            if !attack.is_zero() {
+                node.insert(VolumeFade::new(Volume::SILENT, Volume::UNITY_GAIN, attack));
+            }
+        });
+    }
+
+    /// Restarts this envelope on the volume node that was spawned for a
+    /// previous sound of the given sample player, so the node is reused rather
+    /// than spawned again.
+    pub(super) fn restart_node(&self, sample_player: &mut EntityCommands) {
+        let attack = self.attack;
+        sample_player.queue_silenced(move |mut entity: EntityWorldMut| {
+            let Some(effects) = entity.get::<SampleEffects>() else {
+                return;
+            };
+            let effects = effects.iter().collect::<Vec<_>>();
+
+            entity.world_scope(|world| {
+                for effect in effects {
+                    let Ok(mut node) = world.get_entity_mut(effect) else {
+                        continue;
+                    };
+                    if !node.contains::<EnvelopeNode>() {
+                        continue;
+                    }
+
+                    if let Some(mut volume_node) = node.get_mut::<VolumeNode>() {
+                        volume_node.volume = if attack.is_zero() {
+                            Volume::UNITY_GAIN
+                        } else {
+                            Volume::SILENT
+                        };
+                    }
+                    if attack.is_zero() {
+                        node.remove::<VolumeFade>();
+                    } else {
+                        node.insert(VolumeFade::new(Volume::SILENT, Volume::UNITY_GAIN, attack));
+                    }
+                }
+            });
+        });
+    }
+}
+
+/// Marker component for the [`VolumeNode`] that applies a [`SoundEnvelope`].
+#[derive(Component)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+struct EnvelopeNode;
+
+/// Fades the [`VolumeNode`] on the same entity from one volume to another.
+#[derive(Component)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub(super) struct VolumeFade {
+    from: Volume,
+    to: Volume,
+    timer: Timer,
+    /// The entity to despawn once the fade finishes, if any.
+    despawn_on_finish: Option<Entity>,
+}
+
+impl VolumeFade {
+    pub(super) fn new(from: Volume, to: Volume, duration: Duration) -> Self {
+        VolumeFade {
+            from,
+            to,
+            timer: Timer::new(duration, TimerMode::Once),
+            despawn_on_finish: None,
+        }
+    }
+
+    /// Despawns the given entity once the fade finishes.
+    pub(super) fn then_despawn(mut self, entity: Entity) -> Self {
+        self.despawn_on_finish = Some(entity);
+        self
+    }
+
+    /// The volume at the current point of the fade.
+    fn volume(&self) -> Volume {
+        let (from, to) = (self.from.linear(), self.to.linear());
+        Volume::Linear(from + (to - from) * self.timer.fraction())
+    }
+}
+
+fn tick_volume_fades(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut VolumeFade, &mut VolumeNode)>,
+) {
+    for (entity, mut fade, mut node) in &mut query {
+        fade.timer.tick(time.delta());
+        node.volume = fade.volume();
+
+        if fade.timer.is_finished() {
+            commands.entity(entity).try_remove::<VolumeFade>();
+            if let Some(despawn) = fade.despawn_on_finish {
+                commands.entity(despawn).try_despawn();
+            }
+        }
+    }
+}
+
+/// Stops every sound player child of the target entity, fading out the ones
+/// that have a release or fade time.
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn on_stop_sound_effect(
+    stop: On<StopSoundEffect>,
+    mut commands: Commands,
+    children_query: Query<&Children>,
+    envelope_query: Query<(&SoundEnvelope, Option<&SampleEffects>)>,
+    node_query: Query<&VolumeNode, With<EnvelopeNode>>,
+) {
+    debug!("Stopping sound effect");
+
+    // Stop the random looping and sequential sound effects from moving on to
+    // their next sound once the current one is gone, and drop every request
+    // that would start a sound later on.
+    commands
+        .entity(stop.entity)
+        .try_insert(SoundEffectStopped)
+        .try_remove::<(
+            RandomLoopingSoundEffect,
+            SequentialSoundEffect,
+            PendingSoundEffect,
+            AwaitingPacket,
+            PendingNextSound,
+            IdleSoundPlayer,
+            VirtualSoundEffect,
+        )>();
+
+    let Ok(children) = children_query.get(stop.entity) else {
+        return;
+    };
+
+    for child in children.iter() {
+        let Ok((envelope, effects)) = envelope_query.get(child) else {
+            continue;
+        };
+
+        let node = effects.and_then(|effects| {
+            effects
+                .iter()
+                .find_map(|effect| node_query.get(effect).ok().map(|node| (effect, node)))
+        });
+
+        let release = stop.fade.unwrap_or(envelope.release);
+
+        match node {
+            Some((node_entity, node)) if !release.is_zero() => {
+                debug!(?release, "Releasing sound");
+                commands.entity(node_entity).try_insert(
+                    VolumeFade::new(node.volume, Volume::SILENT, release).then_despawn(child),
+                );
+            }
+            _ => {
+                commands.entity(child).try_despawn();
+            }
+        }
+    }
+}
+
+/// Despawns sound players that are spawned for a sound effect that is already
+/// stopped, e.g., when it was stopped in the same frame it was started.
+fn on_voice_added(
+    add: On<Add, Voice>,
+    mut commands: Commands,
+    query: Query<&ChildOf>,
+    stopped_query: Query<(), With<SoundEffectStopped>>,
+) {
+    let Ok(child_of) = query.get(add.entity) else {
+        return;
+    };
+
+    if stopped_query.contains(child_of.parent()) {
+        commands.entity(add.entity).try_despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    const EPSILON: f32 = 1e-5;
+
+    /// An app with only the envelope plugin, whose time only moves with
+    /// [`advance`].
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>();
+        app.add_plugins(EnvelopePlugin);
+        app
+    }
+
+    fn advance(app: &mut App, duration: Duration) {
+        app.world_mut().resource_mut::<Time>().advance_by(duration);
+        app.update();
+    }
+
+    /// Spawns a sound effect with a sound player child that has the given
+    /// envelope, returning both.
+    fn spawn_sound_player(app: &mut App, envelope: SoundEnvelope) -> (Entity, Entity) {
+        let world = app.world_mut();
+        let sound_effect = world.spawn_empty().id();
+        let sound_player = world.spawn((envelope, ChildOf(sound_effect))).id();
+        envelope.spawn_node(&mut world.commands().entity(sound_player));
+        world.flush();
+        (sound_effect, sound_player)
+    }
+
+    fn envelope_node(app: &App, sound_player: Entity) -> Entity {
+        let effects = app.world().get::<SampleEffects>(sound_player).unwrap();
+        effects.iter().next().unwrap()
+    }
+
+    fn volume(app: &App, node: Entity) -> f32 {
+        app.world().get::<VolumeNode>(node).unwrap().volume.linear()
+    }
+
+    fn stop(app: &mut App, stop: StopSoundEffect) {
+        let world = app.world_mut();
+        world.trigger(stop);
+        world.flush();
+    }
+
+    fn exists(app: &App, entity: Entity) -> bool {
+        app.world().get_entity(entity).is_ok()
+    }
+
+    #[test]
+    fn attack_fades_in() {
+        let mut app = app();
+        let envelope = SoundEnvelope {
+            attack: SECOND,
+            release: Duration::ZERO,
+        };
+        let (_, sound_player) = spawn_sound_player(&mut app, envelope);
+        let node = envelope_node(&app, sound_player);
+
+        assert!(volume(&app, node).abs() < EPSILON);
+
+        advance(&mut app, SECOND / 2);
+        assert!((volume(&app, node) - 0.5).abs() < EPSILON);
+
+        advance(&mut app, SECOND / 2);
+        assert!((volume(&app, node) - 1.0).abs() < EPSILON);
+        assert!(!app.world().entity(node).contains::<VolumeFade>());
+        assert!(exists(&app, sound_player));
+    }
+
+    #[test]
+    fn release_fades_out_then_despawns() {
+        let mut app = app();
+        let envelope = SoundEnvelope {
+            attack: Duration::ZERO,
+            release: SECOND,
+        };
+        let (sound_effect, sound_player) = spawn_sound_player(&mut app, envelope);
+        let node = envelope_node(&app, sound_player);
+
+        stop(&mut app, StopSoundEffect::new(sound_effect));
+        assert!(app.world().entity(node).contains::<VolumeFade>());
+
+        advance(&mut app, SECOND / 2);
+        assert!((volume(&app, node) - 0.5).abs() < EPSILON);
+        assert!(exists(&app, sound_player));
+
+        advance(&mut app, SECOND / 2);
+        assert!(!exists(&app, sound_player));
+        assert!(
+            app.world()
+                .entity(sound_effect)
+                .contains::<SoundEffectStopped>()
+        );
+    }
+
+    #[test]
+    fn zero_length_envelope_plays_at_full_volume_and_stops_at_once() {
+        let mut app = app();
+        let (sound_effect, sound_player) = spawn_sound_player(&mut app, SoundEnvelope::default());
+        let node = envelope_node(&app, sound_player);
+
+        assert!((volume(&app, node) - 1.0).abs() < EPSILON);
+        assert!(!app.world().entity(node).contains::<VolumeFade>());
+
+        stop(&mut app, StopSoundEffect::new(sound_effect));
+
+        assert!(!exists(&app, sound_player));
+    }
+
+    #[test]
+    fn restarted_node_fades_in_again() {
+        let mut app = app();
+        let envelope = SoundEnvelope {
+            attack: SECOND,
+            release: Duration::ZERO,
+        };
+        let (_, sound_player) = spawn_sound_player(&mut app, envelope);
+        let node = envelope_node(&app, sound_player);
+        advance(&mut app, SECOND);
+
+        let world = app.world_mut();
+        envelope.restart_node(&mut world.commands().entity(sound_player));
+        world.flush();
+
+        assert!(volume(&app, node).abs() < EPSILON);
+        assert!(app.world().entity(node).contains::<VolumeFade>());
+    }
+
+    #[test]
+    fn stop_with_fade_drops_pending_requests() {
+        let mut app = app();
+        let (sound_effect, sound_player) = spawn_sound_player(&mut app, SoundEnvelope::default());
+        app.world_mut()
+            .entity_mut(sound_effect)
+            .insert((PendingSoundEffect, PendingNextSound));
+        let node = envelope_node(&app, sound_player);
+
+        stop(
+            &mut app,
+            StopSoundEffect::new(sound_effect).with_fade(SECOND),
+        );
+
+        let stopped = app.world().entity(sound_effect);
+        assert!(stopped.contains::<SoundEffectStopped>());
+        assert!(!stopped.contains::<PendingSoundEffect>());
+        assert!(!stopped.contains::<PendingNextSound>());
+        // The fade overrides the zero release of the sound.
+        assert!(app.world().entity(node).contains::<VolumeFade>());
+
+        advance(&mut app, SECOND);
+        assert!(!exists(&app, sound_player));
+    }
+}
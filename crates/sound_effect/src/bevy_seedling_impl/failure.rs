@@ -0,0 +1,253 @@
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use darkomen::sound::sfx::SfxId;
+use derive_more::derive::{Display, Error};
+#[allow(unused_imports)]
+use tracing::*;
+
+pub(super) struct PlaybackFailurePlugin {
+    pub(super) policy: PlaybackFailurePolicy,
+}
+
+impl Plugin for PlaybackFailurePlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(feature = "reflect")]
+        {
+            app.register_type::<PlaybackFailurePolicy>();
+            app.register_type::<FailedSoundEffect>();
+        }
+
+        app.insert_resource(self.policy);
+
+        app.add_observer(on_sound_effect_playback_failed);
+    }
+}
+
+/// The reason a sound effect could not be played.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Display, Eq, Error, Hash, PartialEq)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Debug, Hash, PartialEq))]
+pub enum SoundEffectPlaybackError {
+    /// No packet is known for the requested packet path.
+    #[display("packet does not exist")]
+    PacketNotFound,
+    /// The packet is known but its asset is not loaded.
+    #[display("packet asset not loaded")]
+    PacketNotLoaded,
+    /// The packet or one of its audio samples failed to load.
+    #[display("packet asset failed to load")]
+    PacketLoadFailed,
+    /// The packet did not finish loading within the packet load timeout.
+    #[display("packet asset did not load in time")]
+    PacketLoadTimedOut,
+    /// The packet has no sound effect with the requested ID.
+    #[display("sound effect does not exist")]
+    SoundEffectNotFound,
+    /// The sound effect has no sounds to play.
+    #[display("sound effect has no sounds")]
+    NoSounds,
+    /// The packet has no audio sample for one of the sound effect's sounds.
+    #[display("audio sample handle does not exist")]
+    AudioSampleNotFound,
+    /// The voice budget is exhausted and no playing voice could be stolen.
+    #[display("voice budget exhausted")]
+    VoiceLimitReached,
+}
+
+/// Triggered when a sound effect requested on an entity could not be played.
+#[derive(Clone, EntityEvent)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct SoundEffectPlaybackFailed {
+    /// The entity the sound effect was requested on.
+    pub entity: Entity,
+    /// The path of the packet the sound effect was requested from, if known.
+    pub packet_path: Option<String>,
+    /// The ID of the sound effect within the packet.
+    pub sound_effect_id: SfxId,
+    /// Why the sound effect could not be played.
+    pub reason: SoundEffectPlaybackError,
+}
+
+/// What to do with an entity whose sound effect could not be played.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Resource)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(
+    feature = "reflect",
+    derive(Reflect),
+    reflect(Default, PartialEq, Resource)
+)]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub enum PlaybackFailurePolicy {
+    /// Leave the entity as it is.
+    #[default]
+    Keep,
+    /// Insert a [`FailedSoundEffect`] on the entity.
+    Tag,
+    /// Despawn the entity.
+    Despawn,
+}
+
+/// Inserted on an entity whose sound effect could not be played when the
+/// [`PlaybackFailurePolicy`] is [`PlaybackFailurePolicy::Tag`].
+#[derive(Clone, Component, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct FailedSoundEffect {
+    pub reason: SoundEffectPlaybackError,
+}
+
+/// Logs the failure and triggers [`SoundEffectPlaybackFailed`] for the entity.
+///
+/// Running out of voices is part of normal play and a packet that takes too
+/// long to load may still be fixed by the user, so only the other reasons are
+/// logged as errors.
+pub(super) fn report_playback_failure(
+    commands: &mut Commands,
+    entity: Entity,
+    packet_path: Option<String>,
+    sound_effect_id: SfxId,
+    reason: SoundEffectPlaybackError,
+) {
+    match reason {
+        SoundEffectPlaybackError::VoiceLimitReached => {
+            debug!(%reason, "Sound effect playback failed");
+        }
+        SoundEffectPlaybackError::PacketLoadTimedOut => {
+            warn!(%reason, "Sound effect playback failed");
+        }
+        _ => {
+            error!(%reason, "Sound effect playback failed");
+        }
+    }
+
+    commands.trigger(SoundEffectPlaybackFailed {
+        entity,
+        packet_path,
+        sound_effect_id,
+        reason,
+    });
+}
+
+fn on_sound_effect_playback_failed(
+    failed: On<SoundEffectPlaybackFailed>,
+    mut commands: Commands,
+    policy: Res<PlaybackFailurePolicy>,
+) {
+    match *policy {
+        PlaybackFailurePolicy::Keep => {}
+        PlaybackFailurePolicy::Tag => {
+            commands
+                .entity(failed.entity)
+                .try_insert(FailedSoundEffect {
+                    reason: failed.reason,
+                });
+        }
+        PlaybackFailurePolicy::Despawn => {
+            commands.entity(failed.entity).try_despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(policy: PlaybackFailurePolicy) -> App {
+        let mut app = App::new();
+        app.add_plugins(PlaybackFailurePlugin { policy });
+        app
+    }
+
+    fn fail(app: &mut App, entity: Entity, reason: SoundEffectPlaybackError) {
+        let world = app.world_mut();
+        report_playback_failure(
+            &mut world.commands(),
+            entity,
+            Some("DARKOMEN/SOUND/H/BIRDS.H".to_string()),
+            3,
+            reason,
+        );
+        world.flush();
+    }
+
+    #[test]
+    fn triggers_failed_event() {
+        let mut app = app(PlaybackFailurePolicy::Keep);
+        app.init_resource::<Failures>();
+        app.add_observer(
+            |failed: On<SoundEffectPlaybackFailed>, mut failures: ResMut<Failures>| {
+                failures.0.push(failed.event().clone());
+            },
+        );
+        let entity = app.world_mut().spawn_empty().id();
+
+        fail(&mut app, entity, SoundEffectPlaybackError::NoSounds);
+
+        let failures = &app.world().resource::<Failures>().0;
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].entity, entity);
+        assert_eq!(
+            failures[0].packet_path.as_deref(),
+            Some("DARKOMEN/SOUND/H/BIRDS.H")
+        );
+        assert_eq!(failures[0].sound_effect_id, 3);
+        assert_eq!(failures[0].reason, SoundEffectPlaybackError::NoSounds);
+    }
+
+    #[test]
+    fn keep_policy_leaves_entity() {
+        let mut app = app(PlaybackFailurePolicy::Keep);
+        let entity = app.world_mut().spawn_empty().id();
+
+        fail(&mut app, entity, SoundEffectPlaybackError::PacketNotFound);
+
+        let entity = app.world().entity(entity);
+        assert!(!entity.contains::<FailedSoundEffect>());
+    }
+
+    #[test]
+    fn tag_policy_inserts_failed_sound_effect() {
+        let mut app = app(PlaybackFailurePolicy::Tag);
+        let entity = app.world_mut().spawn_empty().id();
+
+        fail(
+            &mut app,
+            entity,
+            SoundEffectPlaybackError::VoiceLimitReached,
+        );
+
+        let failed = app.world().get::<FailedSoundEffect>(entity).unwrap();
+        assert_eq!(failed.reason, SoundEffectPlaybackError::VoiceLimitReached);
+    }
+
+    #[test]
+    fn despawn_policy_despawns_entity() {
+        let mut app = app(PlaybackFailurePolicy::Despawn);
+        let entity = app.world_mut().spawn_empty().id();
+
+        fail(
+            &mut app,
+            entity,
+            SoundEffectPlaybackError::PacketLoadTimedOut,
+        );
+
+        assert!(app.world().get_entity(entity).is_err());
+    }
+
+    #[test]
+    fn despawned_entity_is_ignored() {
+        let mut app = app(PlaybackFailurePolicy::Tag);
+        let entity = app.world_mut().spawn_empty().id();
+        app.world_mut().despawn(entity);
+
+        fail(&mut app, entity, SoundEffectPlaybackError::NoSounds);
+
+        assert!(app.world().get_entity(entity).is_err());
+    }
+
+    #[derive(Default, Resource)]
+    struct Failures(Vec<SoundEffectPlaybackFailed>);
+}
@@ -0,0 +1,133 @@
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_rand::prelude::*;
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use rand::{RngCore, SeedableRng};
+
+pub(super) struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(
+        &self,
+        #[allow(
+            unused_variables,
+            reason = "Only used to register types with the reflect feature"
+        )]
+        app: &mut App,
+    ) {
+        #[cfg(feature = "reflect")]
+        app.register_type::<SoundEffectRng>();
+    }
+}
+
+/// Insert this component alongside a [`SoundEffect`] to draw its random sound
+/// choices and playback rates from its own RNG instead of the global one.
+///
+/// The sequence then only depends on the seed, not on how much entropy other
+/// systems consumed, so replays, tests and lockstep games hear the same sounds
+/// for the same seed. The component must be present when the sound effect is
+/// added, e.g., in the same bundle.
+///
+/// [`SoundEffect`]: crate::SoundEffect
+#[derive(Clone, Component)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct SoundEffectRng(WyRand);
+
+impl SoundEffectRng {
+    /// Creates a new RNG from a stable seed.
+    pub fn from_seed(seed: u64) -> Self {
+        SoundEffectRng(WyRand::seed_from_u64(seed))
+    }
+
+    /// Forks a new RNG from the given one, e.g., the global [`WyRand`] or
+    /// another seeded RNG, so a single seed can drive many emitters.
+    pub fn fork(rng: &mut impl RngCore) -> Self {
+        SoundEffectRng(WyRand::from_rng(rng))
+    }
+}
+
+/// Picks the RNG of the emitter if it has one, or the global RNG otherwise.
+#[inline(always)]
+pub(super) fn emitter_rng<'a>(
+    emitter_rng: Option<Mut<'a, SoundEffectRng>>,
+    global_rng: &'a mut WyRand,
+) -> &'a mut WyRand {
+    match emitter_rng {
+        Some(emitter_rng) => &mut emitter_rng.into_inner().0,
+        None => global_rng,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use bevy_asset::prelude::*;
+
+    use super::*;
+    use crate::{
+        asset::PacketAsset,
+        selection::{SoundSelectionState, select_sound},
+        sound_extension::SoundExt as _,
+    };
+
+    const SAMPLE_RATE: NonZeroU32 = NonZeroU32::new(48_000).unwrap();
+
+    /// The file stems and playback rates of the first sounds the `BIRDS.H`
+    /// random looping sound effect plays with the given RNG.
+    fn picks(rng: &mut SoundEffectRng) -> Vec<(String, f64)> {
+        let packet = PacketAsset::birds();
+        let sound_effect = packet.sound_effect(0).unwrap();
+        let mut state = SoundSelectionState::default();
+
+        (0..32)
+            .map(|_| {
+                let sound =
+                    select_sound(&mut rng.0, &packet, sound_effect, None, &mut state).unwrap();
+                let (_, playback_settings) =
+                    sound.audio_sample_settings(&mut rng.0, SAMPLE_RATE, &Handle::default());
+                (sound.file_stem, playback_settings.speed)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_plays_same_sounds() {
+        let first = picks(&mut SoundEffectRng::from_seed(42));
+        let second = picks(&mut SoundEffectRng::from_seed(42));
+
+        assert_eq!(first, second);
+        // The packet varies both, so an equal sequence is not a coincidence.
+        assert!(first.iter().any(|pick| pick.0 != first[0].0));
+        assert!(first.iter().any(|pick| pick.1 != first[0].1));
+    }
+
+    #[test]
+    fn different_seeds_play_different_sounds() {
+        let first = picks(&mut SoundEffectRng::from_seed(1));
+        let second = picks(&mut SoundEffectRng::from_seed(2));
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn fork_of_seeded_global_rng_is_stable() {
+        // What the global RNG is with `EntropyPlugin::<WyRand>::with_seed`.
+        let forks = |seed: u64| {
+            let mut global_rng = WyRand::seed_from_u64(seed);
+            let first = picks(&mut SoundEffectRng::fork(&mut global_rng));
+            let second = picks(&mut SoundEffectRng::fork(&mut global_rng));
+            (first, second)
+        };
+
+        let (first, second) = forks(7);
+
+        assert_eq!((first.clone(), second.clone()), forks(7));
+        // Each fork draws from the global RNG, so emitters forked one after
+        // the other do not play in unison.
+        assert_ne!(first, second);
+    }
+}
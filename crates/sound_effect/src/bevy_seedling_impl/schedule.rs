@@ -0,0 +1,96 @@
+use core::{cmp::Reverse, time::Duration};
+use std::collections::BinaryHeap;
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_time::prelude::*;
+#[allow(unused_imports)]
+use tracing::*;
+
+use super::{PendingNextSound, PendingSoundEffect};
+
+pub(super) struct SchedulePlugin;
+
+impl Plugin for SchedulePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NextSoundSchedule>();
+    }
+}
+
+/// The random looping sound effects waiting out the gap before their next
+/// sound, and the sound effects waiting to take back a stolen voice, ordered by
+/// when they are due.
+///
+/// A single queue serves every emitter, so that waiting costs nothing per
+/// frame beyond peeking at the earliest entry.
+#[derive(Default, Resource)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub(super) struct NextSoundSchedule {
+    queue: BinaryHeap<Reverse<(Duration, Entity, ScheduledPlay)>>,
+}
+
+/// What is played once a scheduled entry is due.
+#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+enum ScheduledPlay {
+    /// The next sound of a random looping or sequential sound effect.
+    NextSound,
+    /// The sound effect from the start.
+    Restart,
+}
+
+impl NextSoundSchedule {
+    /// Plays the next sound of the given sound effect once `at` has passed.
+    pub(super) fn schedule(&mut self, at: Duration, entity: Entity) {
+        self.queue
+            .push(Reverse((at, entity, ScheduledPlay::NextSound)));
+    }
+
+    /// Starts the given sound effect over once `at` has passed.
+    pub(super) fn schedule_restart(&mut self, at: Duration, entity: Entity) {
+        self.queue
+            .push(Reverse((at, entity, ScheduledPlay::Restart)));
+    }
+
+    /// Returns `true` if the given sound effect is still waiting for a
+    /// scheduled entry.
+    pub(super) fn is_scheduled(&self, entity: Entity) -> bool {
+        self.queue
+            .iter()
+            .any(|Reverse((_, scheduled, _))| *scheduled == entity)
+    }
+}
+
+/// Hands the sound effects whose gap has ended over to
+/// [`play_pending_next_sounds`](super::play_pending_next_sounds), and the ones
+/// to start over to
+/// [`play_pending_sound_effects`](super::play_pending_sound_effects).
+///
+/// Sound effects that were stopped or despawned in the meantime are skipped
+/// there.
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+pub(super) fn release_scheduled_next_sounds(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut schedule: ResMut<NextSoundSchedule>,
+) {
+    let now = time.elapsed();
+
+    while let Some(&Reverse((at, entity, play))) = schedule.queue.peek() {
+        if at > now {
+            break;
+        }
+        schedule.queue.pop();
+
+        match play {
+            ScheduledPlay::NextSound => {
+                debug!(?entity, "Gap before next sound ended");
+                commands.entity(entity).try_insert(PendingNextSound);
+            }
+            ScheduledPlay::Restart => {
+                debug!(?entity, "Restarting sound effect");
+                commands.entity(entity).try_insert(PendingSoundEffect);
+            }
+        }
+    }
+}
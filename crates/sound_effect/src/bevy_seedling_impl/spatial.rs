@@ -0,0 +1,139 @@
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_platform::collections::HashSet;
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use bevy_seedling::{
+    SeedlingSystems,
+    firewheel::{
+        dsp::distance_attenuation::{DistanceAttenuation, DistanceModel},
+        nodes::spatial_basic::SpatialBasicNode,
+    },
+    prelude::*,
+};
+#[allow(unused_imports)]
+use tracing::*;
+
+use crate::SoundEffect;
+
+pub(super) struct SpatialPlugin {
+    pub(super) settings: SpatialSoundEffectSettings,
+}
+
+impl Plugin for SpatialPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone());
+
+        app.add_systems(Last, sync_spatial_settings.before(SeedlingSystems::Acquire));
+
+        #[cfg(feature = "reflect")]
+        app.register_type::<SpatialSoundEffectSettings>();
+    }
+}
+
+/// Settings for spatial sound effects.
+///
+/// As a resource, these are the defaults of every spatial sound effect, set
+/// with [`SoundEffectPlugin::with_spatial_settings`]. As a component next to a
+/// [`SoundEffect`], they override the defaults for that emitter, so a loud
+/// emitter can carry further than a quiet one.
+///
+/// [`SoundEffect`]: crate::SoundEffect
+/// [`SoundEffectPlugin::with_spatial_settings`]: crate::SoundEffectPlugin::with_spatial_settings
+#[derive(Clone, Component, Resource)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(
+    feature = "reflect",
+    derive(Reflect),
+    reflect(Component, Default, Resource)
+)]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct SpatialSoundEffectSettings {
+    /// Distance attenuation settings for spatial audio.
+    pub distance_attenuation: DistanceAttenuation,
+}
+
+impl Default for SpatialSoundEffectSettings {
+    fn default() -> Self {
+        Self {
+            distance_attenuation: DistanceAttenuation {
+                distance_model: DistanceModel::Linear,
+                reference_distance: 2.0,
+                max_distance: 50.0,
+                ..DistanceAttenuation::default()
+            },
+        }
+    }
+}
+
+impl SpatialSoundEffectSettings {
+    /// Sets how the volume falls off with distance.
+    pub fn with_distance_model(mut self, distance_model: DistanceModel) -> Self {
+        self.distance_attenuation.distance_model = distance_model;
+        self
+    }
+
+    /// Sets the distance up to which the sound plays at full volume.
+    pub fn with_reference_distance(mut self, reference_distance: f32) -> Self {
+        self.distance_attenuation.reference_distance = reference_distance;
+        self
+    }
+
+    /// Sets the distance beyond which the sound no longer gets quieter.
+    pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+        self.distance_attenuation.max_distance = max_distance;
+        self
+    }
+
+    /// Creates a new [`SpatialBasicNode`] using these settings.
+    pub(super) fn create_node(&self) -> SpatialBasicNode {
+        SpatialBasicNode {
+            distance_attenuation: self.distance_attenuation,
+            ..SpatialBasicNode::default()
+        }
+    }
+}
+
+/// Pushes changes to the spatial settings, global or per emitter, to the
+/// spatial nodes of the sounds that are already playing, so attenuation can be
+/// tuned while listening.
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn sync_spatial_settings(
+    settings: Res<SpatialSoundEffectSettings>,
+    mut removed: RemovedComponents<SpatialSoundEffectSettings>,
+    emitter_query: Query<
+        (Entity, Option<Ref<SpatialSoundEffectSettings>>, &Children),
+        With<SoundEffect>,
+    >,
+    effects_query: Query<&SampleEffects>,
+    mut node_query: Query<&mut SpatialBasicNode>,
+) {
+    // Emitters whose override was removed fall back to the global settings.
+    let removed = removed.read().collect::<HashSet<_>>();
+
+    for (entity, emitter_settings, children) in &emitter_query {
+        let (settings, changed) = match &emitter_settings {
+            Some(emitter_settings) => (&**emitter_settings, emitter_settings.is_changed()),
+            None => (
+                &*settings,
+                settings.is_changed() || removed.contains(&entity),
+            ),
+        };
+        if !changed {
+            continue;
+        }
+
+        debug!(?entity, "Updating spatial settings of sound effect");
+
+        for child in children.iter() {
+            let Ok(effects) = effects_query.get(child) else {
+                continue;
+            };
+
+            let mut iter = node_query.iter_many_mut(effects);
+            while let Some(mut node) = iter.fetch_next() {
+                node.distance_attenuation = settings.distance_attenuation;
+            }
+        }
+    }
+}
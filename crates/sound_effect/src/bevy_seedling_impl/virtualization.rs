@@ -0,0 +1,544 @@
+use core::time::Duration;
+
+use bevy_app::prelude::*;
+use bevy_asset::prelude::*;
+use bevy_ecs::{prelude::*, system::SystemParam};
+use bevy_platform::collections::HashMap;
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use bevy_seedling::spatial::SpatialListener3D;
+use bevy_time::prelude::*;
+use bevy_transform::{helper::TransformHelper, prelude::*};
+use darkomen::sound::sfx::SfxId;
+#[allow(unused_imports)]
+use tracing::*;
+
+use super::{
+    IdleSoundPlayer, NextSoundSchedule, PendingNextSound, PendingSoundEffect, SoundCategoryPool,
+    SoundEffectStopped, SoundEffectSystems, SpatialSoundEffectSettings, is_looped,
+};
+use crate::{
+    PacketAssetHandle, RandomLoopingSoundEffect, SequentialSoundEffect, SoundEffect, SoundEffectId,
+    asset::PacketAsset,
+};
+
+pub(super) struct VirtualizationPlugin {
+    pub(super) settings: Option<VirtualizationSettings>,
+}
+
+impl Plugin for VirtualizationPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(settings) = &self.settings {
+            app.insert_resource(settings.clone());
+        }
+
+        #[cfg(feature = "reflect")]
+        {
+            app.register_type::<VirtualizationSettings>();
+            app.register_type::<VirtualSoundEffect>();
+        }
+
+        app.init_resource::<SoundDurations>();
+
+        app.add_systems(
+            Last,
+            update_virtual_sound_effects
+                .run_if(resource_exists::<VirtualizationSettings>)
+                .before(SoundEffectSystems::Play),
+        );
+    }
+}
+
+/// Turns spatial sound effects that are too far from the [`SpatialListener3D`]
+/// to be heard into [`VirtualSoundEffect`]s, which play nothing until the
+/// listener comes close again.
+///
+/// A sound effect is out of earshot beyond the `max_distance` of its
+/// [`SpatialSoundEffectSettings`], and starts out virtual if it already is when
+/// requested. Virtualization is off unless this resource exists, e.g., when set
+/// with [`SoundEffectPlugin::with_virtualization`].
+///
+/// [`SoundEffectPlugin::with_virtualization`]: crate::SoundEffectPlugin::with_virtualization
+#[derive(Clone, Resource)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Resource))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct VirtualizationSettings {
+    /// How much further than its `max_distance` a sound effect must be before
+    /// it becomes virtual, so that a listener moving around the edge does not
+    /// restart it over and over.
+    pub hysteresis: f32,
+}
+
+impl Default for VirtualizationSettings {
+    fn default() -> Self {
+        VirtualizationSettings { hysteresis: 5.0 }
+    }
+}
+
+impl VirtualizationSettings {
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+}
+
+/// Marker component for a spatial sound effect that is out of earshot and has
+/// no sound players or audio nodes.
+///
+/// Time keeps passing for a virtual sound effect:
+/// - Random looping sound effects keep waiting out their gap, and play their
+///   next sound once they are real again and the gap is over.
+/// - Sequential sound effects skip the sounds they would have played in the
+///   meantime, and wait for the one that would be playing to end before
+///   continuing. This relies on how long their sounds played for before, so a
+///   sequence that was never heard continues with its next sound.
+/// - Looped sounds start over, as a sound cannot be started part way through,
+///   and one-shot sounds are over.
+#[derive(Clone, Component, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct VirtualSoundEffect {
+    since: Duration,
+}
+
+impl VirtualSoundEffect {
+    pub(super) fn new(since: Duration) -> Self {
+        VirtualSoundEffect { since }
+    }
+
+    /// The elapsed [`Time`] at which the sound effect became virtual.
+    #[inline(always)]
+    pub fn since(&self) -> Duration {
+        self.since
+    }
+
+    /// How long the sound effect has been virtual for.
+    #[inline(always)]
+    pub fn elapsed(&self, now: Duration) -> Duration {
+        now.saturating_sub(self.since)
+    }
+}
+
+/// Tells whether a spatial sound effect that is about to start is too far
+/// from the [`SpatialListener3D`] to be heard, so that it starts out as a
+/// [`VirtualSoundEffect`].
+#[derive(SystemParam)]
+pub(super) struct Earshot<'w, 's> {
+    settings: Option<Res<'w, VirtualizationSettings>>,
+    listener_query: Query<'w, 's, &'static GlobalTransform, With<SpatialListener3D>>,
+    transform_helper: TransformHelper<'w, 's>,
+}
+
+impl Earshot<'_, '_> {
+    /// Returns `true` if virtualization is on, the sound effect is spatial and
+    /// the emitter is further from the listener than the `max_distance` of its
+    /// spatial settings.
+    ///
+    /// The emitter's transform is computed from its hierarchy, as sound
+    /// effects are usually requested before their [`GlobalTransform`] is
+    /// propagated.
+    pub(super) fn is_out_of_earshot(
+        &self,
+        entity: Entity,
+        request: &SoundEffect,
+        spatial_settings: &SpatialSoundEffectSettings,
+    ) -> bool {
+        if self.settings.is_none() || !request.is_spatial() {
+            return false;
+        }
+        let Some(listener_transform) = self.listener_query.iter().next() else {
+            return false;
+        };
+        let Ok(transform) = self.transform_helper.compute_global_transform(entity) else {
+            return false;
+        };
+
+        let distance = transform
+            .translation()
+            .distance(listener_transform.translation());
+        distance > spatial_settings.distance_attenuation.max_distance
+    }
+}
+
+/// How long each sound of the sequential sound effects played for, measured
+/// as they finish, so that a virtual sequence knows which sounds it would have
+/// played in the meantime.
+#[derive(Default, Resource)]
+pub(super) struct SoundDurations(HashMap<(AssetId<PacketAsset>, SfxId, usize), Duration>);
+
+impl SoundDurations {
+    /// Records how long the sound at `index` of a sequential sound effect
+    /// played for.
+    pub(super) fn record(
+        &mut self,
+        packet: AssetId<PacketAsset>,
+        sound_effect_id: SfxId,
+        index: usize,
+        duration: Duration,
+    ) {
+        self.0.insert((packet, sound_effect_id, index), duration);
+    }
+
+    fn get(
+        &self,
+        packet: AssetId<PacketAsset>,
+        sound_effect_id: SfxId,
+        index: usize,
+    ) -> Option<Duration> {
+        self.0.get(&(packet, sound_effect_id, index)).copied()
+    }
+}
+
+/// Where a sequence of `len` sounds would be by now, if its sound at `current`
+/// started `elapsed` ago.
+///
+/// Returns the index of the next sound to play and how long to wait before
+/// playing it. The walk stops at the first sound whose duration is not known:
+/// the sound that started last is then assumed to be over, and any later one
+/// is played from its start.
+fn sequence_progress(
+    current: usize,
+    len: usize,
+    looping: bool,
+    mut elapsed: Duration,
+    duration: impl Fn(usize) -> Option<Duration>,
+) -> (usize, Duration) {
+    let duration = |index| duration(index).filter(|duration| !duration.is_zero());
+
+    // Whole laps of a looping sequence change nothing, so skip them rather
+    // than walk through every sound of a sequence that was virtual for long.
+    if looping {
+        let lap = (0..len).map(&duration).sum::<Option<Duration>>();
+        if let Some(lap) = lap.filter(|lap| !lap.is_zero()) {
+            elapsed = Duration::from_nanos((elapsed.as_nanos() % lap.as_nanos()) as u64);
+        }
+    }
+
+    let mut index = current;
+    loop {
+        let Some(sound_duration) = duration(index) else {
+            let next_index = if index == current { index + 1 } else { index };
+            return (next_index, Duration::ZERO);
+        };
+        if elapsed < sound_duration {
+            return (index + 1, sound_duration - elapsed);
+        }
+
+        elapsed -= sound_duration;
+        index += 1;
+        if index >= len {
+            if !looping {
+                return (len, Duration::ZERO);
+            }
+            index = 0;
+        }
+    }
+}
+
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn update_virtual_sound_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<VirtualizationSettings>,
+    spatial_settings: Res<SpatialSoundEffectSettings>,
+    packet_assets: Res<Assets<PacketAsset>>,
+    durations: Res<SoundDurations>,
+    mut schedule: ResMut<NextSoundSchedule>,
+    listener_query: Query<&GlobalTransform, With<SpatialListener3D>>,
+    mut emitter_query: Query<
+        (
+            Entity,
+            &SoundEffect,
+            &GlobalTransform,
+            Option<&SpatialSoundEffectSettings>,
+            Option<&Children>,
+            Option<&VirtualSoundEffect>,
+            Option<(&PacketAssetHandle, &SoundEffectId)>,
+            Has<RandomLoopingSoundEffect>,
+            Option<&mut SequentialSoundEffect>,
+        ),
+        Without<SoundEffectStopped>,
+    >,
+    player_query: Query<&SoundCategoryPool>,
+) {
+    let Some(listener_transform) = listener_query.iter().next() else {
+        return;
+    };
+    let listener_translation = listener_transform.translation();
+    let now = time.elapsed();
+
+    for (
+        entity,
+        request,
+        transform,
+        emitter_spatial_settings,
+        children,
+        virtual_sound_effect,
+        sound_effect,
+        random_looping,
+        sequential,
+    ) in &mut emitter_query
+    {
+        if !request.is_spatial() {
+            continue;
+        }
+
+        let max_distance = emitter_spatial_settings
+            .unwrap_or(&spatial_settings)
+            .distance_attenuation
+            .max_distance;
+        let distance = transform.translation().distance(listener_translation);
+
+        match virtual_sound_effect {
+            None if distance > max_distance + settings.hysteresis => {
+                // Only the players of sounds that are actually played in the
+                // world, the emitter may have been made spatial after they
+                // started.
+                let players = children
+                    .into_iter()
+                    .flat_map(|children| children.iter())
+                    .filter(|&child| player_query.get(child).is_ok_and(|pool| pool.spatial))
+                    .collect::<Vec<_>>();
+                if players.is_empty() {
+                    continue;
+                }
+
+                debug!(?entity, ?distance, "Virtualizing sound effect");
+
+                // The marker goes first so that the removal observer does not
+                // move on to the next sound when the players are despawned.
+                commands
+                    .entity(entity)
+                    .try_insert(VirtualSoundEffect { since: now })
+                    .try_remove::<(PendingNextSound, IdleSoundPlayer)>();
+                for player in players {
+                    commands.entity(player).try_despawn();
+                }
+            }
+            Some(virtual_sound_effect) if distance <= max_distance => {
+                debug!(
+                    ?entity,
+                    ?distance,
+                    elapsed = ?virtual_sound_effect.elapsed(now),
+                    "Sound effect in earshot again, making it real"
+                );
+
+                // A next sound that came due while virtual is played below,
+                // unless there is still a gap or retry to wait out.
+                commands
+                    .entity(entity)
+                    .try_remove::<(VirtualSoundEffect, PendingNextSound)>();
+                if schedule.is_scheduled(entity) {
+                    continue;
+                }
+
+                if let Some(mut sequential) = sequential {
+                    let delay = advance_sequence(
+                        &packet_assets,
+                        &durations,
+                        sound_effect,
+                        &mut sequential,
+                        now,
+                    );
+                    if delay.is_zero() {
+                        commands.entity(entity).try_insert(PendingNextSound);
+                    } else {
+                        debug!(?entity, ?delay, "Waiting for the skipped sequential sound");
+                        schedule.schedule(now + delay, entity);
+                    }
+                } else if random_looping {
+                    commands.entity(entity).try_insert(PendingNextSound);
+                } else if is_looped(&packet_assets, sound_effect) {
+                    commands.entity(entity).try_insert(PendingSoundEffect);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Skips the sounds a sequential sound effect would have played while it was
+/// virtual, returning how long to wait before its next sound.
+fn advance_sequence(
+    packet_assets: &Assets<PacketAsset>,
+    durations: &SoundDurations,
+    sound_effect: Option<(&PacketAssetHandle, &SoundEffectId)>,
+    sequential: &mut SequentialSoundEffect,
+    now: Duration,
+) -> Duration {
+    let Some((packet_handle, sound_effect_id)) = sound_effect else {
+        return Duration::ZERO;
+    };
+    let Some(len) = packet_assets
+        .get(packet_handle.id())
+        .and_then(|packet| packet.sound_effect(sound_effect_id.0))
+        .map(|sound_effect| sound_effect.sounds.len())
+    else {
+        return Duration::ZERO;
+    };
+    let Some(current) = sequential
+        .next_index
+        .checked_sub(1)
+        .filter(|&current| current < len)
+    else {
+        return Duration::ZERO;
+    };
+
+    let (next_index, delay) = sequence_progress(
+        current,
+        len,
+        sequential.looping,
+        now.saturating_sub(sequential.started),
+        |index| durations.get(packet_handle.id(), sound_effect_id.0, index),
+    );
+    sequential.next_index = next_index;
+    delay
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::Vec3;
+
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn known(durations: &[u64]) -> impl Fn(usize) -> Option<Duration> {
+        move |index| durations.get(index).map(|&secs| Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn current_sound_still_playing() {
+        let progress = sequence_progress(0, 3, false, SECOND, known(&[3, 3, 3]));
+
+        assert_eq!(progress, (1, 2 * SECOND));
+    }
+
+    #[test]
+    fn skips_sounds_that_would_have_played() {
+        let progress = sequence_progress(0, 3, false, 4 * SECOND, known(&[3, 3, 3]));
+
+        assert_eq!(progress, (2, 2 * SECOND));
+    }
+
+    #[test]
+    fn finishes_sequence() {
+        let progress = sequence_progress(1, 3, false, 10 * SECOND, known(&[3, 3, 3]));
+
+        assert_eq!(progress, (3, Duration::ZERO));
+    }
+
+    #[test]
+    fn wraps_looping_sequence() {
+        let progress = sequence_progress(2, 3, true, 4 * SECOND, known(&[3, 3, 3]));
+
+        assert_eq!(progress, (1, 2 * SECOND));
+    }
+
+    #[test]
+    fn skips_whole_laps() {
+        let progress = sequence_progress(
+            0,
+            2,
+            true,
+            Duration::from_secs(60 * 60 * 24) + SECOND,
+            known(&[2, 3]),
+        );
+
+        assert_eq!(progress, (1, SECOND));
+    }
+
+    #[test]
+    fn unknown_current_duration_plays_next() {
+        let progress = sequence_progress(1, 3, false, 10 * SECOND, known(&[3]));
+
+        assert_eq!(progress, (2, Duration::ZERO));
+    }
+
+    #[test]
+    fn unknown_later_duration_plays_that_sound() {
+        let progress = sequence_progress(0, 3, false, 10 * SECOND, known(&[3]));
+
+        assert_eq!(progress, (1, Duration::ZERO));
+    }
+
+    #[test]
+    fn zero_durations_are_unknown() {
+        let progress = sequence_progress(0, 2, true, 10 * SECOND, known(&[0, 0]));
+
+        assert_eq!(progress, (1, Duration::ZERO));
+    }
+
+    /// An app that only virtualizes sound effects, with the listener at the
+    /// origin.
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>();
+        app.init_resource::<Assets<PacketAsset>>();
+        app.init_resource::<NextSoundSchedule>();
+        app.init_resource::<SpatialSoundEffectSettings>();
+        app.add_plugins(VirtualizationPlugin {
+            settings: Some(VirtualizationSettings::default()),
+        });
+        app.world_mut()
+            .spawn((SpatialListener3D::default(), GlobalTransform::IDENTITY));
+        app
+    }
+
+    fn move_to(app: &mut App, entity: Entity, x: f32) {
+        app.world_mut()
+            .entity_mut(entity)
+            .insert(GlobalTransform::from_translation(Vec3::X * x));
+        app.update();
+    }
+
+    #[test]
+    fn spatial_emitter_back_in_earshot_is_real_again() {
+        let mut app = app();
+        let max_distance = SpatialSoundEffectSettings::default()
+            .distance_attenuation
+            .max_distance;
+        let emitter = app
+            .world_mut()
+            .spawn((
+                SoundEffect::new(Handle::default(), 0).spatial(),
+                RandomLoopingSoundEffect,
+                VirtualSoundEffect::new(Duration::ZERO),
+                GlobalTransform::from_translation(Vec3::X * max_distance * 2.0),
+            ))
+            .id();
+
+        app.update();
+        assert!(app.world().entity(emitter).contains::<VirtualSoundEffect>());
+
+        move_to(&mut app, emitter, max_distance + 1.0);
+        assert!(app.world().entity(emitter).contains::<VirtualSoundEffect>());
+
+        move_to(&mut app, emitter, max_distance - 1.0);
+        let emitter = app.world().entity(emitter);
+        assert!(!emitter.contains::<VirtualSoundEffect>());
+        assert!(emitter.contains::<PendingNextSound>());
+    }
+
+    #[test]
+    fn non_spatial_emitter_is_left_alone() {
+        let mut app = app();
+        let emitter = app
+            .world_mut()
+            .spawn((
+                SoundEffect::new(Handle::default(), 0),
+                RandomLoopingSoundEffect,
+                VirtualSoundEffect::new(Duration::ZERO),
+                GlobalTransform::IDENTITY,
+            ))
+            .id();
+
+        app.update();
+
+        let emitter = app.world().entity(emitter);
+        assert!(emitter.contains::<VirtualSoundEffect>());
+        assert!(!emitter.contains::<PendingNextSound>());
+    }
+}
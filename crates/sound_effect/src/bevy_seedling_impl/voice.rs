@@ -0,0 +1,291 @@
+use core::time::Duration;
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_platform::collections::HashMap;
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+#[allow(unused_imports)]
+use tracing::*;
+
+/// The default maximum number of sound effect voices that can play at once.
+pub const DEFAULT_MAX_VOICES: usize = 64;
+
+/// How long a random looping, sequential or looped sound effect whose voice was
+/// stolen, or that found no voice for its next sound, waits before competing
+/// for a voice again.
+pub(super) const VOICE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+pub(super) struct VoicePlugin {
+    pub(super) max_voices: usize,
+}
+
+impl Plugin for VoicePlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(feature = "reflect")]
+        {
+            app.register_type::<Voice>();
+            app.register_type::<StolenVoice>();
+        }
+
+        app.insert_resource(Voices::new(self.max_voices));
+
+        app.add_observer(on_voice_removed);
+    }
+}
+
+/// A sound player child that counts against the voice budget.
+#[derive(Clone, Component, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub(super) struct Voice {
+    /// The packet `PRIORITY` of the sound effect. Higher values are more
+    /// important.
+    priority: u8,
+    /// The linear volume the sound was started with.
+    volume: f32,
+    /// Whether the voice may be stolen at all.
+    interruptible: bool,
+}
+
+impl Voice {
+    pub(super) fn new(priority: u8, volume: f32, interruptible: bool) -> Self {
+        Voice {
+            priority,
+            volume,
+            interruptible,
+        }
+    }
+
+    /// Returns `true` if this voice may be stolen to make room for `other`.
+    #[inline(always)]
+    fn yields_to(&self, other: &Voice) -> bool {
+        self.priority < other.priority
+            || (self.priority == other.priority && self.volume < other.volume)
+    }
+}
+
+/// Marker component for a sound player child whose voice was stolen, so that
+/// its sound effect waits before trying to take a voice back rather than moving
+/// on to its next sound right away.
+#[derive(Component)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub(super) struct StolenVoice;
+
+/// Tracks the voices that are currently playing against a global budget.
+///
+/// Voices are tracked here as soon as they are spawned, rather than queried,
+/// so that sounds started in the same frame see each other.
+#[derive(Resource)]
+pub(super) struct Voices {
+    max_voices: usize,
+    active: HashMap<Entity, (Entity, Voice)>,
+}
+
+impl Voices {
+    fn new(max_voices: usize) -> Self {
+        Voices {
+            max_voices,
+            active: HashMap::default(),
+        }
+    }
+
+    /// Makes room for a new voice, stealing the least important voice if the
+    /// budget is exhausted.
+    ///
+    /// Only the sound of the stolen voice ends. Its sound effect keeps going
+    /// and competes for a voice again after [`VOICE_RETRY_DELAY`].
+    ///
+    /// Returns `false` if the budget is exhausted and no voice yields to the
+    /// new one, in which case the sound should not be played.
+    pub(super) fn make_room(&mut self, commands: &mut Commands, voice: &Voice) -> bool {
+        if self.active.len() < self.max_voices {
+            return true;
+        }
+
+        let Some((&victim, &(_, victim_voice))) = self
+            .active
+            .iter()
+            .filter(|(_, (_, voice))| voice.interruptible)
+            .min_by(|(_, (_, a)), (_, (_, b))| {
+                a.priority
+                    .cmp(&b.priority)
+                    .then(a.volume.total_cmp(&b.volume))
+            })
+        else {
+            debug!(
+                priority = voice.priority,
+                "Voice budget exhausted and no voice can be stolen, rejecting sound"
+            );
+            return false;
+        };
+
+        if !victim_voice.yields_to(voice) {
+            debug!(
+                priority = voice.priority,
+                "Voice budget exhausted, rejecting sound"
+            );
+            return false;
+        }
+
+        debug!(
+            priority = voice.priority,
+            victim_priority = victim_voice.priority,
+            "Voice budget exhausted, stealing voice"
+        );
+
+        self.active.remove(&victim);
+        commands
+            .entity(victim)
+            .try_insert(StolenVoice)
+            .try_despawn();
+
+        true
+    }
+
+    /// Starts tracking a voice that has just been spawned.
+    pub(super) fn insert(&mut self, entity: Entity, parent: Entity, voice: Voice) {
+        self.active.insert(entity, (parent, voice));
+    }
+
+    /// Stops tracking a voice, freeing its slot in the budget.
+    pub(super) fn release(&mut self, entity: Entity) {
+        self.active.remove(&entity);
+    }
+}
+
+fn on_voice_removed(remove: On<Remove, Voice>, mut voices: ResMut<Voices>) {
+    voices.release(remove.entity);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::world::CommandQueue;
+
+    use super::*;
+
+    /// Spawns the voices into a budget they exhaust, returning the budget and
+    /// the entities of the voices.
+    fn exhausted_budget(world: &mut World, playing: &[Voice]) -> (Voices, Vec<Entity>) {
+        let mut voices = Voices::new(playing.len());
+        let parent = world.spawn_empty().id();
+        let entities = playing
+            .iter()
+            .map(|&voice| {
+                let entity = world.spawn(voice).id();
+                voices.insert(entity, parent, voice);
+                entity
+            })
+            .collect();
+        (voices, entities)
+    }
+
+    fn make_room(world: &mut World, voices: &mut Voices, voice: Voice) -> bool {
+        let mut queue = CommandQueue::default();
+        let made_room = voices.make_room(&mut Commands::new(&mut queue, world), &voice);
+        queue.apply(world);
+        made_room
+    }
+
+    fn is_playing(world: &World, entity: Entity) -> bool {
+        world.get_entity(entity).is_ok()
+    }
+
+    #[test]
+    fn yields_to_higher_priority_then_louder() {
+        let voice = Voice::new(100, 0.5, true);
+
+        assert!(voice.yields_to(&Voice::new(101, 0.1, true)));
+        assert!(voice.yields_to(&Voice::new(100, 0.6, true)));
+        assert!(!voice.yields_to(&Voice::new(100, 0.5, true)));
+        assert!(!voice.yields_to(&Voice::new(99, 1.0, true)));
+    }
+
+    #[test]
+    fn steals_lowest_priority() {
+        let mut world = World::new();
+        let (mut voices, playing) = exhausted_budget(
+            &mut world,
+            &[
+                Voice::new(100, 1.0, true),
+                Voice::new(50, 1.0, true),
+                Voice::new(200, 1.0, true),
+            ],
+        );
+
+        let voice = Voice::new(150, 1.0, true);
+        assert!(make_room(&mut world, &mut voices, voice));
+
+        assert!(is_playing(&world, playing[0]));
+        assert!(!is_playing(&world, playing[1]));
+        assert!(is_playing(&world, playing[2]));
+        assert_eq!(voices.active.len(), 2);
+    }
+
+    #[test]
+    fn steals_quieter_voice_of_equal_priority() {
+        let mut world = World::new();
+        let (mut voices, playing) = exhausted_budget(
+            &mut world,
+            &[Voice::new(100, 1.0, true), Voice::new(100, 0.5, true)],
+        );
+
+        let voice = Voice::new(100, 0.8, true);
+        assert!(make_room(&mut world, &mut voices, voice));
+
+        assert!(is_playing(&world, playing[0]));
+        assert!(!is_playing(&world, playing[1]));
+    }
+
+    #[test]
+    fn never_steals_uninterruptible_voices() {
+        let mut world = World::new();
+        let (mut voices, playing) = exhausted_budget(
+            &mut world,
+            &[Voice::new(10, 0.1, false), Voice::new(200, 1.0, true)],
+        );
+
+        let voice = Voice::new(100, 1.0, true);
+        assert!(!make_room(&mut world, &mut voices, voice));
+
+        assert!(playing.iter().all(|&entity| is_playing(&world, entity)));
+        assert_eq!(voices.active.len(), 2);
+    }
+
+    #[test]
+    fn rejects_when_every_voice_outranks_the_new_one() {
+        let mut world = World::new();
+        let (mut voices, playing) = exhausted_budget(
+            &mut world,
+            &[Voice::new(200, 0.5, true), Voice::new(150, 1.0, true)],
+        );
+
+        let voice = Voice::new(100, 1.0, true);
+        assert!(!make_room(&mut world, &mut voices, voice));
+
+        assert!(playing.iter().all(|&entity| is_playing(&world, entity)));
+    }
+
+    #[test]
+    fn respects_max_voices() {
+        let mut app = App::new();
+        app.add_plugins(VoicePlugin { max_voices: 2 });
+        let parent = app.world_mut().spawn_empty().id();
+
+        let mut voices = app.world_mut().remove_resource::<Voices>().unwrap();
+        let world = app.world_mut();
+        for _ in 0..2 {
+            let voice = Voice::new(100, 1.0, true);
+            assert!(make_room(world, &mut voices, voice));
+            let entity = world.spawn(voice).id();
+            voices.insert(entity, parent, voice);
+        }
+
+        // The budget is exhausted, so an equal voice has nothing to steal.
+        assert!(!make_room(world, &mut voices, Voice::new(100, 1.0, true)));
+        assert_eq!(voices.active.len(), 2);
+    }
+}
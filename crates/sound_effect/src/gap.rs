@@ -0,0 +1,146 @@
+use core::time::Duration;
+
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// The random silence between two consecutive sounds of a random looping sound
+/// effect.
+///
+/// Without a gap, the next sound starts as soon as the previous one finishes,
+/// which turns sparse ambiences like crickets at night into a continuous wall
+/// of sound. Durations are written in seconds in sidecar files.
+///
+/// Packets do not store a gap, so there is none unless it is set in the
+/// packet's sidecar file or with [`SoundEffect::with_gap`].
+///
+/// [`SoundEffect::with_gap`]: crate::SoundEffect::with_gap
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Serialize)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(
+    feature = "reflect",
+    derive(Reflect),
+    reflect(Default, Deserialize, PartialEq, Serialize)
+)]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct RandomLoopingGap {
+    #[serde(with = "seconds")]
+    pub min: Duration,
+    #[serde(with = "seconds")]
+    pub max: Duration,
+}
+
+impl RandomLoopingGap {
+    /// No gap, the next sound starts as soon as the previous one finishes.
+    pub const ZERO: Self = Self {
+        min: Duration::ZERO,
+        max: Duration::ZERO,
+    };
+
+    /// Creates a gap that is picked uniformly between the given durations.
+    pub fn new(min: Duration, max: Duration) -> Self {
+        RandomLoopingGap {
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+
+    /// Creates a gap that is always the given duration.
+    pub fn fixed(duration: Duration) -> Self {
+        RandomLoopingGap {
+            min: duration,
+            max: duration,
+        }
+    }
+
+    /// Returns `true` if the next sound always starts right away.
+    #[inline(always)]
+    pub fn is_zero(&self) -> bool {
+        self.max.is_zero()
+    }
+
+    /// Picks the duration of the next gap.
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        if self.min >= self.max {
+            self.max
+        } else {
+            rng.random_range(self.min..=self.max)
+        }
+    }
+}
+
+/// Serializes durations as seconds, which is easier to write by hand than
+/// seconds and nanoseconds.
+mod seconds {
+    use core::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer, de::Error as _};
+
+    pub(super) fn serialize<S: Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f32(duration.as_secs_f32())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        let seconds = f32::deserialize(deserializer)?;
+        Duration::try_from_secs_f32(seconds).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    #[test]
+    fn new_orders_bounds() {
+        let gap = RandomLoopingGap::new(Duration::from_secs(6), Duration::from_secs(2));
+
+        assert_eq!(gap.min, Duration::from_secs(2));
+        assert_eq!(gap.max, Duration::from_secs(6));
+    }
+
+    #[test]
+    fn sample_stays_within_bounds() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let gap = RandomLoopingGap::new(Duration::from_secs(2), Duration::from_secs(6));
+
+        for _ in 0..1000 {
+            let duration = gap.sample(&mut rng);
+            assert!(gap.min <= duration && duration <= gap.max);
+        }
+    }
+
+    #[test]
+    fn fixed_and_zero() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let fixed = RandomLoopingGap::fixed(Duration::from_millis(1500));
+
+        assert_eq!(fixed.sample(&mut rng), Duration::from_millis(1500));
+        assert!(!fixed.is_zero());
+        assert!(RandomLoopingGap::ZERO.is_zero());
+        assert!(RandomLoopingGap::default().is_zero());
+        assert_eq!(RandomLoopingGap::ZERO.sample(&mut rng), Duration::ZERO);
+    }
+
+    #[test]
+    fn seconds_round_trip() {
+        let gap = RandomLoopingGap::new(Duration::from_millis(500), Duration::from_secs(4));
+
+        let contents = ron::to_string(&gap).unwrap();
+        assert!(ron::from_str::<RandomLoopingGap>(&contents).unwrap() == gap);
+        assert!(ron::from_str::<RandomLoopingGap>("(min: 0.5, max: 4.0)").unwrap() == gap);
+    }
+
+    #[test]
+    fn negative_seconds_are_an_error() {
+        assert!(ron::from_str::<RandomLoopingGap>("(min: -1.0, max: 4.0)").is_err());
+        assert!(ron::from_str::<RandomLoopingGap>("(min: 1.0, max: inf)").is_err());
+    }
+}
@@ -1,10 +1,13 @@
 pub mod asset;
 
 mod bevy_seedling_impl;
+mod gap;
+mod packet_set;
+mod selection;
 mod sound_effects;
 pub mod sound_extension;
 
-use core::marker::PhantomData;
+use core::{marker::PhantomData, time::Duration};
 
 use bevy_app::prelude::*;
 use bevy_app_ext::prelude::*;
@@ -17,18 +20,40 @@ use bevy_reflect::{GetTypeRegistration, Typed, prelude::*};
 use darkomen::sound::sfx::SfxId;
 use sound_config::prelude::*;
 
+pub use self::bevy_seedling_impl::{
+    DEFAULT_MAX_VOICES, DEFAULT_PACKET_LOAD_TIMEOUT, DopplerSettings, DopplerVelocity,
+    FailedSoundEffect, PacketLoadTimeout, PlaybackFailurePolicy, SoundCategoryBus,
+    SoundCategoryPool, SoundEffectCommandsExt, SoundEffectPlaybackError, SoundEffectPlaybackFailed,
+    SoundEffectRng, SpatialSoundEffectSettings, VirtualSoundEffect, VirtualizationSettings,
+};
+pub use self::packet_set::{
+    LoadedSoundEffectPackets, SoundEffectPacketSet, SoundEffectPacketSetAppExt,
+};
+pub use self::{gap::RandomLoopingGap, selection::SoundSelection};
+
 pub mod prelude {
+    #[doc(hidden)]
+    pub use crate::packet_set::{
+        LoadedSoundEffectPackets, SoundEffectPacketSet, SoundEffectPacketSetAppExt as _,
+    };
     #[doc(hidden)]
     pub use crate::{
-        SoundEffectPacketAssetCollection, SoundEffectPlugin, SpatialSoundEffect,
-        asset::PacketAsset, sound_effects::SoundEffectKey,
+        RandomLoopingGap, SoundEffect, SoundEffectPacketAssetCollection, SoundEffectPlugin,
+        SoundEffectSource, SoundSelection, SpatialSoundEffect, asset::PacketAsset,
+        sound_effects::SoundEffectKey,
     };
 
     #[doc(hidden)]
-    pub use crate::bevy_seedling_impl::{Mode, SoundEffectPlayer};
+    pub use crate::bevy_seedling_impl::{
+        DopplerSettings, DopplerVelocity, FailedSoundEffect, Mode, PacketLoadTimeout,
+        PauseSoundEffect, PlaybackFailurePolicy, ResumeSoundEffect, SoundEffectCommandsExt as _,
+        SoundEffectPaused, SoundEffectPlaybackError, SoundEffectPlaybackFailed, SoundEffectPlayer,
+        SoundEffectRng, SoundEffectStopped, SoundEffectSystems, SpatialSoundEffectSettings,
+        StopSoundEffect, VirtualSoundEffect, VirtualizationSettings,
+    };
 }
 
-use self::asset::*;
+use self::{asset::*, packet_set::PacketSetPlugin};
 
 #[cfg(all(feature = "debug", feature = "reflect"))]
 pub trait SoundEffectKeyRequirements:
@@ -55,44 +80,129 @@ pub trait SoundEffectKeyRequirements:
 #[cfg(all(not(feature = "debug"), not(feature = "reflect")))]
 pub trait SoundEffectKeyRequirements: SoundEffectKey + Send + Sync + 'static {}
 
-pub struct SoundEffectPlugin<SoundEffectKeyT: SoundEffectKeyRequirements>(
-    PhantomData<SoundEffectKeyT>,
-);
+pub struct SoundEffectPlugin<SoundEffectKeyT: SoundEffectKeyRequirements> {
+    max_voices: usize,
+    failure_policy: PlaybackFailurePolicy,
+    packet_load_timeout: Duration,
+    spatial_settings: SpatialSoundEffectSettings,
+    doppler_settings: Option<DopplerSettings>,
+    virtualization_settings: Option<VirtualizationSettings>,
+    _marker: PhantomData<SoundEffectKeyT>,
+}
 
 impl<SoundEffectKeyT: SoundEffectKeyRequirements> SoundEffectPlugin<SoundEffectKeyT> {
     pub fn new() -> Self {
-        SoundEffectPlugin(PhantomData)
+        SoundEffectPlugin {
+            max_voices: DEFAULT_MAX_VOICES,
+            failure_policy: PlaybackFailurePolicy::default(),
+            packet_load_timeout: DEFAULT_PACKET_LOAD_TIMEOUT,
+            spatial_settings: SpatialSoundEffectSettings::default(),
+            doppler_settings: None,
+            virtualization_settings: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the maximum number of sound effect voices that can play at once.
+    ///
+    /// Once the budget is exhausted, a new sound steals the voice of the
+    /// lowest priority, quietest sound that is playing, or is not played if
+    /// every playing sound is more important. Looping sound effects whose voice
+    /// was stolen try to take one back a moment later.
+    pub fn with_max_voices(mut self, max_voices: usize) -> Self {
+        self.max_voices = max_voices;
+        self
+    }
+
+    /// Sets what happens to an entity whose sound effect could not be played.
+    ///
+    /// A [`SoundEffectPlaybackFailed`] is triggered for the entity regardless
+    /// of the policy.
+    pub fn with_failure_policy(mut self, failure_policy: PlaybackFailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+        self
+    }
+
+    /// Sets how long a sound effect waits for its packet to load before
+    /// playback fails with [`SoundEffectPlaybackError::PacketLoadTimedOut`].
+    ///
+    /// Sound effects requested while their packet is still loading start
+    /// playing as soon as it finishes.
+    pub fn with_packet_load_timeout(mut self, packet_load_timeout: Duration) -> Self {
+        self.packet_load_timeout = packet_load_timeout;
+        self
+    }
+
+    /// Sets the default settings of spatial sound effects.
+    ///
+    /// Insert [`SpatialSoundEffectSettings`] next to a [`SoundEffect`] to
+    /// override them for that emitter.
+    pub fn with_spatial_settings(mut self, spatial_settings: SpatialSoundEffectSettings) -> Self {
+        self.spatial_settings = spatial_settings;
+        self
+    }
+
+    /// Shifts the pitch of spatial sound effects that move relative to the
+    /// listener.
+    ///
+    /// Velocities come from [`DopplerVelocity`], or from how far the emitter
+    /// and the listener moved since the last frame.
+    pub fn with_doppler(mut self, doppler_settings: DopplerSettings) -> Self {
+        self.doppler_settings = Some(doppler_settings);
+        self
+    }
+
+    /// Stops playing spatial sound effects that are out of earshot of the
+    /// listener, and plays them again once the listener comes close.
+    ///
+    /// Virtual sound effects have no sample players or audio nodes, so maps
+    /// with many ambient emitters stay cheap.
+    pub fn with_virtualization(mut self, virtualization_settings: VirtualizationSettings) -> Self {
+        self.virtualization_settings = Some(virtualization_settings);
+        self
     }
 }
 
 impl<SoundEffectKeyT: SoundEffectKeyRequirements> Plugin for SoundEffectPlugin<SoundEffectKeyT> {
     fn build(&self, app: &mut App) {
-        app.try_add_plugins(SoundConfigPlugin);
+        app.try_add_plugins(SoundConfigPlugin::default());
         app.try_add_plugins(SoundEffectAssetPlugin);
+        app.try_add_plugins(PacketSetPlugin);
 
-        app.try_add_plugins(crate::bevy_seedling_impl::SoundEffectPlugin::<
-            SoundEffectKeyT,
-        >::new());
+        app.try_add_plugins(
+            crate::bevy_seedling_impl::SoundEffectPlugin::<SoundEffectKeyT> {
+                max_voices: self.max_voices,
+                failure_policy: self.failure_policy,
+                packet_load_timeout: self.packet_load_timeout,
+                spatial_settings: self.spatial_settings.clone(),
+                doppler_settings: self.doppler_settings.clone(),
+                virtualization_settings: self.virtualization_settings.clone(),
+                _marker: PhantomData,
+            },
+        );
 
         #[cfg(feature = "reflect")]
         {
             app.register_type::<SoundEffectPacketAssetCollection>();
             app.register_type::<SoundEffectId>();
             app.register_type::<RandomLoopingSoundEffect>();
+            app.register_type::<SequentialSoundEffect>();
+            app.register_type::<SoundSelection>();
+            app.register_type::<RandomLoopingGap>();
+            app.register_type::<selection::SoundSelectionState>();
+            app.register_type::<SoundEffectOverride>();
+            app.register_type::<SoundEffect>();
             app.register_type::<SpatialSoundEffect>();
         }
     }
 }
 
-// TODO: This causes all packets to be loaded up front, which is not ideal. We
-// should work out how to make screens/scenes specify the packets they only load
-// what they need. Project instances spatial sound effect ID breaks down into a
-// packet path and sound effect ID and based on that packet path, the original
-// only loads the packets needed for that battle. We should be able to do a
-// similar thing, e.g., the "Book" screen only needs to load the book-related/UI
-// sound effect packets. Re the project instances comment here: The BTB file is
-// the one that specifies the "spatial sound effect preset" to load and that
-// preset has N packets that it loads.
+/// Every sound effect packet in the `DARKOMEN/SOUND/H` directory.
+///
+/// Loading this collection loads all packets up front. Screens and scenes that
+/// only need a few packets should spawn a [`SoundEffectPacketSet`] instead.
+/// Sound effects look up packets in the spawned packet sets first and fall back
+/// to this collection.
 #[derive(AssetCollection, Resource)]
 #[cfg_attr(feature = "debug", derive(Debug))]
 #[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
@@ -112,6 +222,11 @@ impl SoundEffectPacketAssetCollection {
 pub trait SoundEffectKey {
     fn get_packet_path(&self) -> String;
     fn get_sound_effect_id(&self) -> u8;
+
+    /// The category the sound effect is mixed in.
+    fn get_category(&self) -> SoundCategory {
+        SoundCategory::SoundEffect
+    }
 }
 
 #[derive(Clone, Component, Copy, Default)]
@@ -126,6 +241,187 @@ pub(crate) struct SoundEffectId(pub(crate) SfxId);
 #[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
 pub(crate) struct RandomLoopingSoundEffect;
 
+#[derive(Clone, Component, Copy, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub(crate) struct SequentialSoundEffect {
+    /// The index of the next sound to play.
+    pub(crate) next_index: usize,
+    /// Whether to start again from the first sound after the last one.
+    pub(crate) looping: bool,
+    /// The elapsed time at which the sound before `next_index` started.
+    pub(crate) started: Duration,
+}
+
+/// Where a [`SoundEffect`] finds its packet.
+#[derive(Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub enum SoundEffectSource {
+    /// A packet path that is resolved against the loaded packets when the
+    /// sound effect starts.
+    Path { packet_path: String, id: SfxId },
+    /// A packet handle the caller already holds.
+    Packet {
+        packet: Handle<PacketAsset>,
+        id: SfxId,
+    },
+}
+
+impl Default for SoundEffectSource {
+    fn default() -> Self {
+        SoundEffectSource::Packet {
+            packet: Handle::default(),
+            id: SfxId::default(),
+        }
+    }
+}
+
+impl SoundEffectSource {
+    /// The ID of the sound effect within its packet.
+    #[inline(always)]
+    pub fn id(&self) -> SfxId {
+        match self {
+            SoundEffectSource::Path { id, .. } | SoundEffectSource::Packet { id, .. } => *id,
+        }
+    }
+
+    /// The path of the packet, if known.
+    pub fn packet_path(&self) -> Option<String> {
+        match self {
+            SoundEffectSource::Path { packet_path, .. } => Some(packet_path.clone()),
+            SoundEffectSource::Packet { packet, .. } => packet.path().map(ToString::to_string),
+        }
+    }
+}
+
+/// Spawn an entity with this component, or add it to your existing entity, to
+/// play a sound effect.
+///
+/// The sound effect is either looked up by key or given as a packet handle and
+/// ID, and is only played in the world if it is made [`spatial`].
+///
+/// [`spatial`]: SoundEffect::spatial
+#[derive(Clone, Component, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct SoundEffect {
+    pub(crate) source: SoundEffectSource,
+    pub(crate) spatial: bool,
+    pub(crate) category: SoundCategory,
+    pub(crate) selection: Option<SoundSelection>,
+    pub(crate) gap: Option<RandomLoopingGap>,
+    pub(crate) uninterruptible: bool,
+}
+
+impl SoundEffect {
+    /// Creates a new sound effect component from a packet handle and ID.
+    pub fn new(packet: Handle<PacketAsset>, id: SfxId) -> Self {
+        SoundEffect {
+            source: SoundEffectSource::Packet { packet, id },
+            spatial: false,
+            category: SoundCategory::default(),
+            selection: None,
+            gap: None,
+            uninterruptible: false,
+        }
+    }
+
+    /// Creates a new sound effect component from a key.
+    pub fn from_key(key: &impl SoundEffectKey) -> Self {
+        SoundEffect {
+            source: SoundEffectSource::Path {
+                packet_path: key.get_packet_path(),
+                id: key.get_sound_effect_id(),
+            },
+            spatial: false,
+            category: key.get_category(),
+            selection: None,
+            gap: None,
+            uninterruptible: false,
+        }
+    }
+
+    /// Plays the sound effect in the world at the entity's position.
+    pub fn spatial(self) -> Self {
+        self.with_spatial(true)
+    }
+
+    /// Sets whether the sound effect is played in the world at the entity's
+    /// position.
+    pub fn with_spatial(mut self, spatial: bool) -> Self {
+        self.spatial = spatial;
+        self
+    }
+
+    /// Sets the category the sound effect is mixed in.
+    pub fn with_category(mut self, category: SoundCategory) -> Self {
+        self.category = category;
+        self
+    }
+
+    /// Sets how the sounds of a random or random looping sound effect are
+    /// picked, overriding the packet's sidecar file.
+    pub fn with_selection(mut self, selection: SoundSelection) -> Self {
+        self.selection = Some(selection);
+        self
+    }
+
+    /// Sets the silence between the sounds of a random looping sound effect,
+    /// overriding the packet's sidecar file. See [`RandomLoopingGap`].
+    pub fn with_gap(mut self, gap: RandomLoopingGap) -> Self {
+        self.gap = Some(gap);
+        self
+    }
+
+    /// Keeps the voice of the sound effect from being stolen by more
+    /// important sound effects once the voice budget is exhausted.
+    pub fn uninterruptible(self) -> Self {
+        self.with_interruptible(false)
+    }
+
+    /// Sets whether a more important sound effect may steal the voice of this
+    /// one once the voice budget is exhausted.
+    pub fn with_interruptible(mut self, interruptible: bool) -> Self {
+        self.uninterruptible = !interruptible;
+        self
+    }
+
+    #[inline(always)]
+    pub fn source(&self) -> &SoundEffectSource {
+        &self.source
+    }
+
+    #[inline(always)]
+    pub fn is_spatial(&self) -> bool {
+        self.spatial
+    }
+
+    #[inline(always)]
+    pub fn category(&self) -> SoundCategory {
+        self.category
+    }
+
+    #[inline(always)]
+    pub fn selection(&self) -> Option<SoundSelection> {
+        self.selection
+    }
+
+    #[inline(always)]
+    pub fn gap(&self) -> Option<RandomLoopingGap> {
+        self.gap
+    }
+
+    #[inline(always)]
+    pub fn is_interruptible(&self) -> bool {
+        !self.uninterruptible
+    }
+}
+
+/// Shorthand for a spatial [`SoundEffect`] given as a packet handle and ID.
 #[derive(Clone, Component, Default)]
 #[cfg_attr(feature = "debug", derive(Debug))]
 #[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
@@ -0,0 +1,273 @@
+use bevy_app::prelude::*;
+use bevy_asset::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_platform::collections::HashMap;
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use bevy_state::prelude::*;
+#[allow(unused_imports)]
+use tracing::*;
+
+use crate::{SoundEffectKey, asset::PacketAsset};
+
+pub(crate) struct PacketSetPlugin;
+
+impl Plugin for PacketSetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoadedSoundEffectPackets>();
+
+        #[cfg(feature = "reflect")]
+        {
+            app.register_type::<SoundEffectPacketSet>();
+            app.register_type::<LoadedSoundEffectPackets>();
+        }
+
+        app.add_observer(on_packet_set_inserted);
+        app.add_observer(on_packet_set_replaced);
+    }
+}
+
+/// Spawn an entity with this component to load the sound effect packets a
+/// screen or scene needs, and despawn it to release them again.
+///
+/// Packets shared by several sets are loaded once and only released when the
+/// last set that needs them is gone. Sound effects resolve their packet paths
+/// against every set that is currently spawned.
+///
+/// See [`SoundEffectPacketSetAppExt::add_sound_effect_packet_set`] to tie a set
+/// to a state.
+#[derive(Clone, Component, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct SoundEffectPacketSet {
+    packet_paths: Vec<String>,
+}
+
+impl SoundEffectPacketSet {
+    /// Creates a new packet set from packet paths, e.g.,
+    /// "DARKOMEN/SOUND/H/GLUE.H".
+    pub fn new(packet_paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let mut packet_paths = packet_paths.into_iter().map(Into::into).collect::<Vec<_>>();
+        packet_paths.sort_unstable();
+        packet_paths.dedup();
+        SoundEffectPacketSet { packet_paths }
+    }
+
+    /// Creates a new packet set from the packets of the given sound effect
+    /// keys.
+    pub fn from_keys<'a, SoundEffectKeyT: SoundEffectKey + 'a>(
+        keys: impl IntoIterator<Item = &'a SoundEffectKeyT>,
+    ) -> Self {
+        Self::new(keys.into_iter().map(SoundEffectKey::get_packet_path))
+    }
+
+    #[inline(always)]
+    pub fn packet_paths(&self) -> &[String] {
+        &self.packet_paths
+    }
+}
+
+/// The packets loaded by every [`SoundEffectPacketSet`] that is currently
+/// spawned.
+#[derive(Default, Resource)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub struct LoadedSoundEffectPackets {
+    /// The packet handles by path, with the number of sets that need them.
+    packets: HashMap<String, (Handle<PacketAsset>, usize)>,
+}
+
+impl LoadedSoundEffectPackets {
+    #[inline(always)]
+    pub fn get(&self, packet_path: &str) -> Option<Handle<PacketAsset>> {
+        self.packets
+            .get(packet_path)
+            .map(|(handle, _)| handle.clone())
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Returns `true` if every packet and its audio samples have loaded.
+    pub fn is_loaded(&self, asset_server: &AssetServer) -> bool {
+        self.packets
+            .values()
+            .all(|(handle, _)| asset_server.is_loaded_with_dependencies(handle.id()))
+    }
+}
+
+pub trait SoundEffectPacketSetAppExt {
+    /// Loads the given packet set when entering the state and releases it when
+    /// exiting the state.
+    fn add_sound_effect_packet_set<S: States>(
+        &mut self,
+        state: S,
+        packet_set: SoundEffectPacketSet,
+    ) -> &mut Self;
+}
+
+impl SoundEffectPacketSetAppExt for App {
+    fn add_sound_effect_packet_set<S: States>(
+        &mut self,
+        state: S,
+        packet_set: SoundEffectPacketSet,
+    ) -> &mut Self {
+        self.add_systems(OnEnter(state.clone()), move |mut commands: Commands| {
+            commands.spawn((
+                #[cfg(feature = "entity_names")]
+                Name::new("Sound effect packet set"),
+                packet_set.clone(),
+                DespawnOnExit(state.clone()),
+            ));
+        })
+    }
+}
+
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn on_packet_set_inserted(
+    insert: On<Insert, SoundEffectPacketSet>,
+    asset_server: Res<AssetServer>,
+    mut loaded: ResMut<LoadedSoundEffectPackets>,
+    query: Query<&SoundEffectPacketSet>,
+) {
+    let Ok(packet_set) = query.get(insert.entity) else {
+        return;
+    };
+
+    for packet_path in packet_set.packet_paths() {
+        loaded
+            .packets
+            .entry(packet_path.clone())
+            .and_modify(|(_, sets)| *sets += 1)
+            .or_insert_with(|| {
+                debug!(packet_path, "Loading sound effect packet");
+                (asset_server.load(packet_path.clone()), 1)
+            });
+    }
+}
+
+/// Releases the packets of a set that is removed, or replaced by another set
+/// on the same entity.
+#[cfg_attr(feature = "instrument", tracing::instrument(skip_all))]
+fn on_packet_set_replaced(
+    replace: On<Replace, SoundEffectPacketSet>,
+    mut loaded: ResMut<LoadedSoundEffectPackets>,
+    query: Query<&SoundEffectPacketSet>,
+) {
+    let Ok(packet_set) = query.get(replace.entity) else {
+        return;
+    };
+
+    for packet_path in packet_set.packet_paths() {
+        let Some((_, sets)) = loaded.packets.get_mut(packet_path) else {
+            continue;
+        };
+
+        *sets -= 1;
+        if *sets == 0 {
+            // Dropping the last strong handle unloads the packet and its audio
+            // samples, unless a playing sound effect still holds it.
+            debug!(packet_path, "Releasing sound effect packet");
+            loaded.packets.remove(packet_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::TaskPoolPlugin;
+
+    use super::*;
+
+    const BIRDS: &str = "DARKOMEN/SOUND/H/BIRDS.H";
+    const GLUE: &str = "DARKOMEN/SOUND/H/GLUE.H";
+
+    #[derive(Clone, Debug, Default, Eq, Hash, PartialEq, States)]
+    enum Screen {
+        #[default]
+        Menu,
+        Battle,
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            TaskPoolPlugin::default(),
+            AssetPlugin::default(),
+            StatesPlugin,
+            PacketSetPlugin,
+        ));
+        app.init_asset::<PacketAsset>();
+        app
+    }
+
+    fn is_loading(app: &App, packet_path: &str) -> bool {
+        app.world()
+            .resource::<LoadedSoundEffectPackets>()
+            .get(packet_path)
+            .is_some()
+    }
+
+    #[test]
+    fn shared_packet_is_released_with_the_last_set() {
+        let mut app = app();
+        let world = app.world_mut();
+        let battle = world.spawn(SoundEffectPacketSet::new([BIRDS, GLUE])).id();
+        let menu = world.spawn(SoundEffectPacketSet::new([GLUE])).id();
+
+        assert!(is_loading(&app, BIRDS));
+        assert!(is_loading(&app, GLUE));
+
+        app.world_mut().despawn(battle);
+        assert!(!is_loading(&app, BIRDS));
+        assert!(is_loading(&app, GLUE));
+
+        app.world_mut().despawn(menu);
+        assert!(
+            app.world()
+                .resource::<LoadedSoundEffectPackets>()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn replaced_set_releases_its_packets() {
+        let mut app = app();
+        let entity = app
+            .world_mut()
+            .spawn(SoundEffectPacketSet::new([BIRDS]))
+            .id();
+
+        app.world_mut()
+            .entity_mut(entity)
+            .insert(SoundEffectPacketSet::new([GLUE]));
+
+        assert!(!is_loading(&app, BIRDS));
+        assert!(is_loading(&app, GLUE));
+    }
+
+    #[test]
+    fn state_loads_and_releases_set() {
+        let mut app = app();
+        app.init_state::<Screen>();
+        app.add_sound_effect_packet_set(Screen::Battle, SoundEffectPacketSet::new([BIRDS]));
+        app.update();
+        assert!(!is_loading(&app, BIRDS));
+
+        app.world_mut()
+            .resource_mut::<NextState<Screen>>()
+            .set(Screen::Battle);
+        app.update();
+        assert!(is_loading(&app, BIRDS));
+
+        app.world_mut()
+            .resource_mut::<NextState<Screen>>()
+            .set(Screen::Menu);
+        app.update();
+        assert!(!is_loading(&app, BIRDS));
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+
+use bevy_ecs::prelude::*;
+#[cfg(feature = "reflect")]
+use bevy_reflect::prelude::*;
+use darkomen::sound::sfx::{Sfx, Sound};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::asset::PacketAsset;
+
+/// How the sounds of a random or random looping sound effect are picked.
+///
+/// Per-sound weights from the packet's sidecar override apply to
+/// [`Self::Random`] and [`Self::AvoidLast`].
+#[derive(Clone, Copy, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(
+    feature = "reflect",
+    derive(Reflect),
+    reflect(Default, Deserialize, Hash, PartialEq, Serialize)
+)]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub enum SoundSelection {
+    /// Pick any sound, like the original game.
+    #[default]
+    Random,
+    /// Play every sound once, in random order, before any sound repeats.
+    ShuffleBag,
+    /// Never pick any of the last N sounds that were played.
+    AvoidLast(usize),
+}
+
+/// The sounds an emitter played recently, so that selection can avoid
+/// repeating them.
+#[derive(Clone, Component, Default)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+#[cfg_attr(all(feature = "reflect", feature = "debug"), reflect(Debug))]
+pub(crate) struct SoundSelectionState {
+    /// The number of sounds the state was built for.
+    sound_count: usize,
+    /// The sounds left to play in the current shuffle-bag cycle.
+    bag: Vec<usize>,
+    /// The most recently played sounds, most recent first.
+    recent: VecDeque<usize>,
+}
+
+impl SoundSelectionState {
+    /// Returns `true` if no sound was selected with this state yet.
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.sound_count == 0
+    }
+
+    /// Picks the index of the next sound out of as many sounds as there are
+    /// weights.
+    fn select(
+        &mut self,
+        rng: &mut impl Rng,
+        selection: SoundSelection,
+        weights: &[f32],
+    ) -> Option<usize> {
+        let sound_count = weights.len();
+        if sound_count == 0 {
+            return None;
+        }
+
+        // The sound effect changed, e.g., the packet was reloaded.
+        if self.sound_count != sound_count {
+            *self = SoundSelectionState {
+                sound_count,
+                ..Default::default()
+            };
+        }
+
+        let index = match selection {
+            SoundSelection::Random => weighted_index(rng, weights, |_| true)?,
+            SoundSelection::ShuffleBag => {
+                if self.bag.is_empty() {
+                    self.bag.extend(0..sound_count);
+                }
+
+                let last = self.recent.front().copied();
+                let mut position = rng.random_range(0..self.bag.len());
+                // Do not play the last sound of a cycle again as the first
+                // sound of the next one.
+                if self.bag.len() > 1 && Some(self.bag[position]) == last {
+                    position = (position + 1) % self.bag.len();
+                }
+                self.bag.swap_remove(position)
+            }
+            SoundSelection::AvoidLast(count) => {
+                // Always leave at least one sound to pick from.
+                let count = count.min(sound_count - 1);
+                let avoided = self.recent.iter().take(count).copied().collect::<Vec<_>>();
+                weighted_index(rng, weights, |index| !avoided.contains(&index))?
+            }
+        };
+
+        self.recent.push_front(index);
+        self.recent.truncate(sound_count);
+
+        Some(index)
+    }
+}
+
+/// Picks an index whose candidate filter passes, weighted by the given weights,
+/// falling back to a uniform pick if all the candidates have no weight.
+///
+/// Weights that are not finite count as no weight.
+fn weighted_index(
+    rng: &mut impl Rng,
+    weights: &[f32],
+    is_candidate: impl Fn(usize) -> bool,
+) -> Option<usize> {
+    let candidates = (0..weights.len())
+        .filter(|&index| is_candidate(index))
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let weight = |index: usize| {
+        let weight = weights[index];
+        if weight.is_finite() {
+            weight.max(0.0)
+        } else {
+            0.0
+        }
+    };
+
+    let total = candidates.iter().map(|&index| weight(index)).sum::<f32>();
+    // The sum of huge weights can still overflow.
+    if !(total > 0.0 && total.is_finite()) {
+        return Some(candidates[rng.random_range(0..candidates.len())]);
+    }
+
+    let mut remaining = rng.random_range(0.0..total);
+    for &index in &candidates {
+        let weight = weight(index);
+        if remaining < weight {
+            return Some(index);
+        }
+        remaining -= weight;
+    }
+
+    // Rounding can leave a sliver of the total unaccounted for.
+    candidates.last().copied()
+}
+
+/// Picks the next sound of a random or random looping sound effect.
+///
+/// The emitter's selection takes precedence over the one in the packet's
+/// sidecar override.
+pub(crate) fn select_sound(
+    rng: &mut impl Rng,
+    packet: &PacketAsset,
+    sound_effect: &Sfx,
+    selection: Option<SoundSelection>,
+    state: &mut SoundSelectionState,
+) -> Option<Sound> {
+    let sound_effect_override = packet.sound_effect_override(sound_effect.id);
+
+    let selection = selection
+        .or(sound_effect_override.and_then(|o| o.selection))
+        .unwrap_or_default();
+    let weights = sound_effect
+        .sounds
+        .iter()
+        .map(|sound| sound_effect_override.map_or(1.0, |o| o.weight(&sound.file_stem)))
+        .collect::<Vec<_>>();
+
+    state
+        .select(rng, selection, &weights)
+        .and_then(|index| sound_effect.sounds.get(index).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(7)
+    }
+
+    #[test]
+    fn no_sounds() {
+        let mut state = SoundSelectionState::default();
+
+        assert_eq!(state.select(&mut rng(), SoundSelection::Random, &[]), None);
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn shuffle_bag_plays_every_sound_once_per_cycle() {
+        let (mut rng, mut state) = (rng(), SoundSelectionState::default());
+        let weights = [1.0; 5];
+
+        let mut last = None;
+        for _ in 0..20 {
+            let mut cycle = (0..weights.len())
+                .map(|_| {
+                    state
+                        .select(&mut rng, SoundSelection::ShuffleBag, &weights)
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+
+            // The next cycle does not start with the sound that ended this one.
+            assert_ne!(Some(cycle[0]), last);
+            last = cycle.last().copied();
+
+            cycle.sort();
+            assert_eq!(cycle, [0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn avoid_last_never_repeats_recent_sounds() {
+        let (mut rng, mut state) = (rng(), SoundSelectionState::default());
+        let weights = [1.0; 4];
+
+        let picks = (0..1000)
+            .map(|_| {
+                state
+                    .select(&mut rng, SoundSelection::AvoidLast(2), &weights)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        for window in picks.windows(3) {
+            assert_ne!(window[2], window[1]);
+            assert_ne!(window[2], window[0]);
+        }
+    }
+
+    #[test]
+    fn avoid_last_leaves_one_sound_to_pick() {
+        let (mut rng, mut state) = (rng(), SoundSelectionState::default());
+
+        let picks = (0..10)
+            .map(|_| {
+                state
+                    .select(&mut rng, SoundSelection::AvoidLast(5), &[1.0, 1.0])
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        for window in picks.windows(2) {
+            assert_ne!(window[0], window[1]);
+        }
+    }
+
+    #[test]
+    fn random_follows_weights() {
+        let (mut rng, mut state) = (rng(), SoundSelectionState::default());
+        let weights = [1.0, 0.0, 3.0];
+
+        let mut counts = [0; 3];
+        for _ in 0..10_000 {
+            let index = state
+                .select(&mut rng, SoundSelection::Random, &weights)
+                .unwrap();
+            counts[index] += 1;
+        }
+
+        assert_eq!(counts[1], 0);
+        let ratio = counts[2] as f32 / counts[0] as f32;
+        assert!((2.7..3.3).contains(&ratio), "ratio is {ratio}");
+    }
+
+    #[test]
+    fn zero_weights_pick_uniformly() {
+        let (mut rng, mut state) = (rng(), SoundSelectionState::default());
+
+        let mut counts = [0; 2];
+        for _ in 0..1000 {
+            let index = state
+                .select(&mut rng, SoundSelection::Random, &[0.0, 0.0])
+                .unwrap();
+            counts[index] += 1;
+        }
+
+        assert!(counts.iter().all(|&count| count > 400));
+    }
+
+    #[test]
+    fn non_finite_weights_do_not_panic() {
+        let (mut rng, mut state) = (rng(), SoundSelectionState::default());
+
+        for _ in 0..100 {
+            let index = state
+                .select(&mut rng, SoundSelection::Random, &[f32::INFINITY, 1.0])
+                .unwrap();
+            assert_eq!(index, 1);
+        }
+
+        for _ in 0..100 {
+            state
+                .select(&mut rng, SoundSelection::Random, &[f32::MAX, f32::MAX])
+                .unwrap();
+            state
+                .select(&mut rng, SoundSelection::Random, &[f32::NAN, f32::NAN])
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn state_resets_when_sound_count_changes() {
+        let (mut rng, mut state) = (rng(), SoundSelectionState::default());
+
+        state.select(&mut rng, SoundSelection::ShuffleBag, &[1.0; 5]);
+        let index = state
+            .select(&mut rng, SoundSelection::ShuffleBag, &[1.0; 2])
+            .unwrap();
+
+        assert!(index < 2);
+        assert_eq!(state.sound_count, 2);
+    }
+}
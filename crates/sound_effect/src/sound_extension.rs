@@ -1,34 +1,41 @@
-use core::num::NonZeroU32;
+use core::{num::NonZeroU32, time::Duration};
 
 use bevy_asset::prelude::*;
 use bevy_seedling::prelude::*;
 use darkomen::sound::sfx::Sound;
 use rand::Rng;
-use sound_config::prelude::*;
 
 pub trait SoundExt {
+    /// The sample player and playback settings of the sound.
+    ///
+    /// The sample player only has the sound's own volume, the [`SoundConfig`]
+    /// volume is applied by the bus of the sound's category so that it can
+    /// change while the sound plays.
+    ///
+    /// [`SoundConfig`]: sound_config::SoundConfig
     fn audio_sample_settings(
         &self,
         rng: &mut impl Rng,
-        sound_config: &SoundConfig,
         sample_rate: NonZeroU32,
         source: &Handle<AudioSample>,
     ) -> (SamplePlayer, PlaybackSettings);
+
+    /// How long the sound takes to fade in when it starts playing.
+    fn attack_duration(&self) -> Duration;
+
+    /// How long the sound takes to fade out when it is stopped early.
+    fn release_duration(&self) -> Duration;
 }
 
 impl SoundExt for Sound {
     fn audio_sample_settings(
         &self,
         rng: &mut impl Rng,
-        sound_config: &SoundConfig,
         sample_rate: NonZeroU32,
         source: &Handle<AudioSample>,
     ) -> (SamplePlayer, PlaybackSettings) {
-        let mut player = SamplePlayer::new(source.clone()).with_volume(Volume::Decibels(
-            sound_config
-                .sound_effect_volume(Volume::Linear(self.linear_volume()).decibels())
-                .0,
-        ));
+        let mut player =
+            SamplePlayer::new(source.clone()).with_volume(Volume::Linear(self.linear_volume()));
 
         if self.looped {
             player = player.looping();
@@ -40,4 +47,19 @@ impl SoundExt for Sound {
                 .with_speed(self.random_playback_rate(rng, sample_rate) as f64),
         )
     }
+
+    #[inline(always)]
+    fn attack_duration(&self) -> Duration {
+        // The unit of the packet `ATTACK` and `RELEASE` fields is not
+        // documented, and every sound of `BIRDS.H` leaves both at 0, so
+        // milliseconds is an assumption: whole seconds would be too coarse for
+        // a fade. A value that does not fit means no fade.
+        Duration::from_millis(u64::try_from(self.attack).unwrap_or(0))
+    }
+
+    #[inline(always)]
+    fn release_duration(&self) -> Duration {
+        // In milliseconds, see `attack_duration`.
+        Duration::from_millis(u64::try_from(self.release).unwrap_or(0))
+    }
 }